@@ -13,9 +13,8 @@ use serde::{Deserialize, Serialize};
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
 
-use awesome_operates::embed::{AssetExtractExt, EXTRACT_SWAGGER_DIR_PATH};
 use awesome_operates::error::Result;
-use awesome_operates::server::server_dir;
+use awesome_operates::server::ServeEmbed;
 use awesome_operates::swagger::InitSwagger;
 
 async fn serve_docs(Extension(api): Extension<Arc<OpenApi>>) -> Response {
@@ -31,11 +30,6 @@ struct User {
     pub name: String,
 }
 
-#[derive(rust_embed::RustEmbed)]
-#[prefix = "embed_files/"]
-#[folder = "src/bin/"]
-pub struct Asset;
-
 async fn example() -> Json<User> {
     Json(User {
         name: "hello".to_owned(),
@@ -52,19 +46,15 @@ async fn server() -> Result<()> {
     aide::gen::on_error(|error| println!("{error}"));
     aide::gen::extract_schemas(true);
     let mut api = OpenApi::default();
-    awesome_operates::embed::Asset::extract().await?;
-    InitSwagger::new(
-        EXTRACT_SWAGGER_DIR_PATH,
-        "swagger-init.js",
-        "index.html",
-        "../api.json",
-    )
-    .build()
-    .await
-    .unwrap();
+    let swagger = InitSwagger::new("", "swagger-init.js", "index.html", "../api.json");
+    // served from memory, nothing is extracted to the disk
+    let swagger_files = ServeEmbed::<awesome_operates::embed::Asset>::new()
+        .prefix("embed_files/swagger/")
+        .with_file(swagger.index_html_filename.as_str(), swagger.index_html())
+        .with_file(swagger.js_filename.as_str(), swagger.initializer_js());
     let app = ApiRouter::new()
         .api_route("/hello", aide::axum::routing::get(example))
-        .nest_service("/swagger/", server_dir(EXTRACT_SWAGGER_DIR_PATH).await)
+        .nest_service("/swagger/", swagger_files)
         .route("/api.json", get(serve_docs))
         .finish_api_with(&mut api, api_docs)
         .layer(
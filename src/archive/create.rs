@@ -0,0 +1,395 @@
+use std::fs::{File, Metadata};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use snafu::ResultExt;
+
+use super::{ArchiveFormat, DEFAULT_MAX_TOTAL_SIZE};
+use crate::error::{
+    ArchiveCreateSnafu, BlockingTaskSnafu, CommonIoSnafu, GlobPatternSnafu, Result,
+};
+
+/// bytes of a chunk `write_stream` writes a packed zip with
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// what is packed and what is left out by `ArchiveBuilder`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    /// entry names in the archive
+    pub files: Vec<String>,
+    /// entry names skipped by the size limits or read errors
+    pub skipped: Vec<String>,
+    /// uncompressed bytes packed
+    pub total_size: u64,
+}
+
+/// pack files and directories into a `.zip` or `.tar.gz`, like a log bundle for support
+/// globs match the path relative to each source, like `*.log` or `**/*.toml`
+/// files over the size limits are skipped and reported in `ArchiveSummary::skipped`
+/// ```rust,no_run
+/// use awesome_operates::archive::{ArchiveBuilder, ArchiveFormat};
+///
+/// let summary = ArchiveBuilder::new(ArchiveFormat::TarGz)
+///     .add_path("/var/log/agent", "logs")
+///     .add_path("/etc/agent/config.toml", "config")
+///     .include("*.log")
+///     .include("*.log.gz")
+///     .include("*.toml")
+///     .exclude("*secret*")
+///     .max_file_size(64 << 20)
+///     .write_file("/tmp/agent-logs.tar.gz")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArchiveBuilder {
+    format: ArchiveFormat,
+    sources: Vec<(PathBuf, String)>,
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    max_file_size: Option<u64>,
+    max_total_size: u64,
+}
+
+impl ArchiveBuilder {
+    pub fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            sources: vec![],
+            includes: vec![],
+            excludes: vec![],
+            max_file_size: None,
+            max_total_size: DEFAULT_MAX_TOTAL_SIZE,
+        }
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// a file or a directory, packed under `name` of the archive, an empty `name` for the root
+    pub fn add_path(mut self, path: impl AsRef<Path>, name: impl Into<String>) -> Self {
+        self.sources
+            .push((path.as_ref().to_path_buf(), name.into()));
+        self
+    }
+
+    /// everything is included when no include globs
+    pub fn include(mut self, glob: impl Into<String>) -> Self {
+        self.includes.push(glob.into());
+        self
+    }
+
+    /// takes precedence over `include`
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.excludes.push(glob.into());
+        self
+    }
+
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// uncompressed bytes of all files, `DEFAULT_MAX_TOTAL_SIZE` by default
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>) -> Result<ArchiveSummary> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(CommonIoSnafu)?;
+        }
+        let file = File::create(path).context(CommonIoSnafu)?;
+        self.write_to(file)
+    }
+
+    pub fn write_to<W: Write + Seek>(&self, writer: W) -> Result<ArchiveSummary> {
+        let mut summary = ArchiveSummary::default();
+        match self.format {
+            ArchiveFormat::Zip => self.write_zip(writer, &mut summary)?,
+            ArchiveFormat::TarGz => self.write_tar_gz(writer, &mut summary)?,
+        }
+        Ok(summary)
+    }
+
+    /// write into a writer can't seek, like a pipe or a response body, no temporary files
+    /// a `.tar.gz` is written as it's packed, a `.zip` is packed in memory by `to_bytes` first,
+    /// then written in chunks
+    /// ```rust
+    /// use awesome_operates::archive::{ArchiveBuilder, ArchiveFormat};
+    ///
+    /// // works without a writable temporary directory
+    /// std::env::set_var("TMPDIR", "/nonexistent");
+    /// let mut streamed = vec![];
+    /// ArchiveBuilder::new(ArchiveFormat::Zip)
+    ///     .add_path("Cargo.toml", "")
+    ///     .write_stream(&mut streamed)
+    ///     .unwrap();
+    /// assert!(streamed.starts_with(b"PK"));
+    /// ```
+    pub fn write_stream<W: Write>(&self, mut writer: W) -> Result<ArchiveSummary> {
+        let summary = match self.format {
+            ArchiveFormat::Zip => {
+                let (bytes, summary) = self.to_bytes()?;
+                for chunk in bytes.chunks(STREAM_CHUNK_SIZE) {
+                    writer.write_all(chunk).context(CommonIoSnafu)?;
+                }
+                summary
+            }
+            ArchiveFormat::TarGz => {
+                let mut summary = ArchiveSummary::default();
+                self.write_tar_gz(&mut writer, &mut summary)?;
+                summary
+            }
+        };
+        writer.flush().context(CommonIoSnafu)?;
+        Ok(summary)
+    }
+
+    fn write_zip<W: Write + Seek>(&self, writer: W, summary: &mut ArchiveSummary) -> Result<()> {
+        let includes = glob_set(&self.includes)?;
+        let excludes = glob_set(&self.excludes)?;
+        let mut zip = zip::ZipWriter::new(writer);
+        self.pack(
+            &includes,
+            &excludes,
+            summary,
+            |name, metadata, size, reader| {
+                let options = zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .large_file(size >= u32::MAX as u64)
+                    .unix_permissions(file_mode(metadata));
+                zip.start_file(name, options).context(ArchiveCreateSnafu)?;
+                std::io::copy(reader, &mut zip).context(CommonIoSnafu)?;
+                Ok(())
+            },
+        )?;
+        zip.finish().context(ArchiveCreateSnafu)?;
+        self.log_summary(summary);
+        Ok(())
+    }
+
+    fn write_tar_gz<W: Write>(&self, writer: W, summary: &mut ArchiveSummary) -> Result<()> {
+        let includes = glob_set(&self.includes)?;
+        let excludes = glob_set(&self.excludes)?;
+        let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+        self.pack(
+            &includes,
+            &excludes,
+            summary,
+            |name, metadata, size, reader| {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(size);
+                header.set_mode(file_mode(metadata));
+                header.set_mtime(
+                    metadata
+                        .modified()
+                        .ok()
+                        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                        .unwrap_or_default()
+                        .as_secs(),
+                );
+                tar.append_data(&mut header, name, reader)
+                    .context(CommonIoSnafu)
+            },
+        )?;
+        tar.into_inner()
+            .context(CommonIoSnafu)?
+            .finish()
+            .context(CommonIoSnafu)?;
+        self.log_summary(summary);
+        Ok(())
+    }
+
+    fn log_summary(&self, summary: &ArchiveSummary) {
+        tracing::info!(
+            "archive {} files, {} bytes, {} skipped",
+            summary.files.len(),
+            summary.total_size,
+            summary.skipped.len()
+        );
+    }
+
+    /// build the whole archive in memory, no temporary files
+    pub fn to_bytes(&self) -> Result<(Vec<u8>, ArchiveSummary)> {
+        let mut buffer = Cursor::new(vec![]);
+        let summary = self.write_to(&mut buffer)?;
+        Ok((buffer.into_inner(), summary))
+    }
+
+    /// `to_bytes` on the blocking thread pool, for async handlers
+    pub async fn to_bytes_blocking(&self) -> Result<(Vec<u8>, ArchiveSummary)> {
+        let builder = self.clone();
+        tokio::task::spawn_blocking(move || builder.to_bytes())
+            .await
+            .context(BlockingTaskSnafu)?
+    }
+
+    /// stream every matched file within the limits to `append`
+    fn pack(
+        &self,
+        includes: &GlobSet,
+        excludes: &GlobSet,
+        summary: &mut ArchiveSummary,
+        mut append: impl FnMut(&str, &Metadata, u64, &mut dyn Read) -> Result<()>,
+    ) -> Result<()> {
+        for (source, name) in &self.sources {
+            let root = if source.is_dir() {
+                source.as_path()
+            } else {
+                source.parent().unwrap_or(Path::new(""))
+            };
+            let walker = walkdir::WalkDir::new(source)
+                .follow_links(false)
+                .sort_by_file_name();
+            for entry in walker {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        tracing::warn!("skip unreadable path under {}: {e}", source.display());
+                        continue;
+                    }
+                };
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                if excludes.is_match(relative)
+                    || (!includes.is_empty() && !includes.is_match(relative))
+                {
+                    continue;
+                }
+                let entry_name = entry_name(name, relative);
+                let remaining = self.max_total_size.saturating_sub(summary.total_size);
+                let limit = self
+                    .max_file_size
+                    .map_or(remaining, |max| max.min(remaining));
+                let Some((metadata, file)) = open_limited(entry.path(), limit) else {
+                    tracing::warn!("skip {entry_name} over the size limit or unreadable");
+                    summary.skipped.push(entry_name);
+                    continue;
+                };
+                let size = metadata.len();
+                // exactly `size` bytes, a file truncated while reading is padded with zeros
+                let mut reader = file.chain(std::io::repeat(0)).take(size);
+                append(&entry_name, &metadata, size, &mut reader)?;
+                summary.total_size += size;
+                summary.files.push(entry_name);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).context(GlobPatternSnafu { pattern })?);
+    }
+    builder.build().context(GlobPatternSnafu {
+        pattern: patterns.join(","),
+    })
+}
+
+/// `/` separated on every platform
+fn entry_name(name: &str, relative: &Path) -> String {
+    let relative = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let name = name.trim_matches('/');
+    if name.is_empty() {
+        relative
+    } else {
+        format!("{name}/{relative}")
+    }
+}
+
+/// the file and its metadata, a growing log is cut at the size when opened
+/// `None` when it's over `limit` bytes
+fn open_limited(path: &Path, limit: u64) -> Option<(Metadata, File)> {
+    let file = File::open(path).ok()?;
+    let metadata = file.metadata().ok()?;
+    (metadata.len() <= limit).then_some((metadata, file))
+}
+
+fn file_mode(metadata: &Metadata) -> u32 {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::unix::fs::PermissionsExt;
+
+            metadata.permissions().mode() & 0o777
+        } else {
+            if metadata.permissions().readonly() {
+                0o444
+            } else {
+                0o644
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveExtractor;
+
+    #[test]
+    fn pack_with_globs_and_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let logs = dir.join("logs");
+        std::fs::create_dir_all(logs.join("old")).unwrap();
+        std::fs::write(logs.join("agent.log"), "running").unwrap();
+        std::fs::write(logs.join("old/agent.log.gz"), "rotated").unwrap();
+        std::fs::write(logs.join("huge.log"), "x".repeat(100)).unwrap();
+        std::fs::write(logs.join("agent.pid"), "42").unwrap();
+        std::fs::write(dir.join("config.toml"), "a = 1").unwrap();
+
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let builder = ArchiveBuilder::new(format)
+                .add_path(&logs, "logs")
+                .add_path(dir.join("config.toml"), "")
+                .include("*.log")
+                .include("*.gz")
+                .include("*.toml")
+                .exclude("old/*")
+                .max_file_size(50);
+            let (bytes, summary) = builder.to_bytes().unwrap();
+            assert_eq!(summary.files, vec!["logs/agent.log", "config.toml"]);
+            assert_eq!(summary.skipped, vec!["logs/huge.log"]);
+            assert_eq!(summary.total_size, 12);
+            let mut streamed = vec![];
+            assert_eq!(builder.write_stream(&mut streamed).unwrap(), summary);
+            assert_eq!(streamed, bytes);
+
+            let extension = format.extension();
+            let archive = dir.join(format!("bundle.{extension}"));
+            std::fs::write(&archive, bytes).unwrap();
+            let target = dir.join(extension);
+            ArchiveExtractor::new(&target).extract(&archive).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(target.join("logs/agent.log")).unwrap(),
+                "running"
+            );
+            assert!(target.join("config.toml").exists());
+        }
+
+        let summary = ArchiveBuilder::new(ArchiveFormat::Zip)
+            .add_path(&logs, "")
+            .max_total_size(10)
+            .write_file(dir.join("limited.zip"))
+            .unwrap();
+        assert_eq!(summary.files, vec!["agent.log", "agent.pid"]);
+        assert!(ArchiveBuilder::new(ArchiveFormat::Zip)
+            .include("[")
+            .to_bytes()
+            .is_err());
+    }
+}
@@ -0,0 +1,493 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use snafu::{OptionExt, ResultExt};
+
+use crate::error::{
+    ArchiveEntryUnsafeSnafu, ArchiveLimitExceededSnafu, ArchiveUnsupportedSnafu, CommonIoSnafu,
+    Result, ZipExtractSnafu,
+};
+
+/// 1GiB
+pub const DEFAULT_MAX_TOTAL_SIZE: u64 = 1 << 30;
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// only permission bits are kept, setuid, setgid and sticky bits from an archive are dropped
+const PERMISSION_MASK: u32 = 0o777;
+const FILE_TYPE_MASK: u32 = 0o170_000;
+const SYMLINK_TYPE: u32 = 0o120_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// by the file name, `.zip`, `.tar.gz` or `.tgz`
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let filename = path.as_ref().file_name()?.to_str()?.to_lowercase();
+        if filename.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// extract `.zip` and `.tar.gz` into an explicit directory
+/// entries with absolute paths or `..` are refused, links and special files are skipped,
+/// the whole archive is checked and extracted into a staging directory beside the target,
+/// which is moved into the target at last, nothing is left in the target when it fails midway
+/// ```rust,no_run
+/// use awesome_operates::archive::ArchiveExtractor;
+///
+/// let files = ArchiveExtractor::new("/opt/agent/plugins")
+///     .max_total_size(256 << 20)
+///     .max_entries(1000)
+///     .extract("/tmp/plugins.tar.gz")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArchiveExtractor {
+    target: PathBuf,
+    max_total_size: u64,
+    max_entries: usize,
+}
+
+impl ArchiveExtractor {
+    pub fn new(target: impl AsRef<Path>) -> Self {
+        Self {
+            target: target.as_ref().to_path_buf(),
+            max_total_size: DEFAULT_MAX_TOTAL_SIZE,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// total uncompressed bytes, `DEFAULT_MAX_TOTAL_SIZE` by default
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    /// `DEFAULT_MAX_ENTRIES` by default
+    pub fn max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// detect the format by `ArchiveFormat::from_path`, return the extracted files
+    pub fn extract(&self, archive: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let archive = archive.as_ref();
+        match ArchiveFormat::from_path(archive) {
+            Some(ArchiveFormat::Zip) => self.extract_zip(archive),
+            Some(ArchiveFormat::TarGz) => self.extract_tar_gz(archive),
+            None => ArchiveUnsupportedSnafu {
+                filepath: archive.display().to_string(),
+            }
+            .fail(),
+        }
+    }
+
+    pub fn extract_zip(&self, archive: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let archive = archive.as_ref();
+        let file = File::open(archive).context(CommonIoSnafu)?;
+        let mut zip = zip::ZipArchive::new(file).context(ZipExtractSnafu)?;
+        let mut budget = Budget::new(self, archive);
+        for index in 0..zip.len() {
+            let entry = zip.by_index_raw(index).context(ZipExtractSnafu)?;
+            budget.check(entry.name(), entry.size())?;
+        }
+
+        self.staged(archive, |staging| {
+            let mut budget = Budget::new(self, archive);
+            let mut extracted = vec![];
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index).context(ZipExtractSnafu)?;
+                let relative = budget.check(entry.name(), entry.size())?;
+                if entry.is_dir() {
+                    create_dirs(staging, &relative, &budget.archive)?;
+                    continue;
+                }
+                let mode = entry.unix_mode();
+                if mode.is_some_and(|mode| mode & FILE_TYPE_MASK == SYMLINK_TYPE) {
+                    tracing::warn!("skip symlink `{}` of {}", entry.name(), archive.display());
+                    continue;
+                }
+                budget.write(&mut entry, staging, &relative, mode)?;
+                extracted.push(relative);
+            }
+            Ok(extracted)
+        })
+    }
+
+    pub fn extract_tar_gz(&self, archive: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let archive = archive.as_ref();
+        let mut budget = Budget::new(self, archive);
+        let mut tar = open_tar_gz(archive)?;
+        for entry in tar.entries().context(CommonIoSnafu)? {
+            let entry = entry.context(CommonIoSnafu)?;
+            let name = entry.path().context(CommonIoSnafu)?;
+            budget.check(&name.to_string_lossy(), entry.size())?;
+        }
+
+        self.staged(archive, |staging| {
+            let mut budget = Budget::new(self, archive);
+            let mut extracted = vec![];
+            let mut tar = open_tar_gz(archive)?;
+            for entry in tar.entries().context(CommonIoSnafu)? {
+                let mut entry = entry.context(CommonIoSnafu)?;
+                let name = entry
+                    .path()
+                    .context(CommonIoSnafu)?
+                    .to_string_lossy()
+                    .to_string();
+                let relative = budget.check(&name, entry.size())?;
+                let entry_type = entry.header().entry_type();
+                if entry_type.is_dir() {
+                    create_dirs(staging, &relative, &budget.archive)?;
+                    continue;
+                }
+                if !entry_type.is_file() {
+                    tracing::warn!(
+                        "skip `{name}` of {}, type {entry_type:?} is not extracted",
+                        archive.display()
+                    );
+                    continue;
+                }
+                let mode = entry.header().mode().ok();
+                budget.write(&mut entry, staging, &relative, mode)?;
+                extracted.push(relative);
+            }
+            Ok(extracted)
+        })
+    }
+
+    /// run `extract` into a fresh staging directory, then move its tree into the target
+    /// `extract` returns the extracted files relative to the staging directory
+    fn staged(
+        &self,
+        archive: &Path,
+        extract: impl FnOnce(&Path) -> Result<Vec<PathBuf>>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut staging = self.target.as_os_str().to_os_string();
+        staging.push(".extracting");
+        let staging = PathBuf::from(staging);
+        let _ = std::fs::remove_dir_all(&staging);
+        let result = std::fs::create_dir_all(&staging)
+            .context(CommonIoSnafu)
+            .and_then(|()| extract(&staging))
+            .and_then(|files| {
+                self.commit(archive, &staging)?;
+                Ok(files
+                    .into_iter()
+                    .map(|relative| self.target.join(relative))
+                    .collect())
+            });
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
+    /// one rename when the target doesn't exist, or move every staged entry into it,
+    /// refused before moving anything when a directory on the way is a symlink
+    fn commit(&self, archive: &Path, staging: &Path) -> Result<()> {
+        let exists =
+            self.target.as_os_str().is_empty() || std::fs::symlink_metadata(&self.target).is_ok();
+        if !exists {
+            if let Some(parent) = self.target.parent() {
+                std::fs::create_dir_all(parent).context(CommonIoSnafu)?;
+            }
+            return std::fs::rename(staging, &self.target).context(CommonIoSnafu);
+        }
+        let archive = archive.display().to_string();
+        let entries = walkdir::WalkDir::new(staging)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .map(|entry| {
+                let entry = entry.map_err(std::io::Error::from).context(CommonIoSnafu)?;
+                let relative = entry.path().strip_prefix(staging).unwrap_or(entry.path());
+                Ok((relative.to_path_buf(), entry.file_type().is_dir()))
+            })
+            .collect::<Result<Vec<(PathBuf, bool)>>>()?;
+        for (relative, is_dir) in &entries {
+            let dirs = if *is_dir {
+                relative.as_path()
+            } else {
+                relative.parent().unwrap_or(Path::new(""))
+            };
+            check_dirs(&self.target, dirs, &archive)?;
+        }
+        for (relative, is_dir) in entries {
+            if is_dir {
+                create_dirs(&self.target, &relative, &archive)?;
+                continue;
+            }
+            let path = self.target.join(&relative);
+            // never write through a symlink or into a running binary left at the path
+            let _ = std::fs::remove_file(&path);
+            std::fs::rename(staging.join(&relative), path).context(CommonIoSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+fn open_tar_gz(archive: &Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    let file = File::open(archive).context(CommonIoSnafu)?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// the path relative to the target directory, empty for the directory itself
+/// `None` when it escapes the target, like `../etc/passwd` or `/etc/passwd`
+pub(crate) fn entry_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// refuse when an existing component of `relative` under `root` is a symlink or not a directory
+fn check_dirs(root: &Path, relative: &Path, archive: &str) -> Result<()> {
+    let mut path = root.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => {
+                return ArchiveEntryUnsafeSnafu {
+                    archive,
+                    entry: relative.display().to_string(),
+                }
+                .fail()
+            }
+            // the rest doesn't exist either
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// `create_dir_all` without following symlinks on the way
+fn create_dirs(root: &Path, relative: &Path, archive: &str) -> Result<()> {
+    check_dirs(root, relative, archive)?;
+    std::fs::create_dir_all(root.join(relative)).context(CommonIoSnafu)
+}
+
+/// entries and bytes consumed of the limits
+struct Budget<'a> {
+    extractor: &'a ArchiveExtractor,
+    archive: String,
+    entries: usize,
+    declared: u64,
+    copied: u64,
+}
+
+impl<'a> Budget<'a> {
+    fn new(extractor: &'a ArchiveExtractor, archive: &Path) -> Self {
+        Self {
+            extractor,
+            archive: archive.display().to_string(),
+            entries: 0,
+            declared: 0,
+            copied: 0,
+        }
+    }
+
+    /// count the entry and its declared size, return the safe relative path
+    fn check(&mut self, name: &str, declared: u64) -> Result<PathBuf> {
+        self.entries += 1;
+        if self.entries > self.extractor.max_entries {
+            return self.exceeded(format!("{} entries", self.extractor.max_entries));
+        }
+        self.declared = self.declared.saturating_add(declared);
+        if self.declared > self.extractor.max_total_size {
+            return self.exceeded(format!("{} bytes", self.extractor.max_total_size));
+        }
+        entry_path(name).context(ArchiveEntryUnsafeSnafu {
+            archive: &self.archive,
+            entry: name,
+        })
+    }
+
+    /// the declared size may lie, so the real bytes are counted again while copying
+    fn write(
+        &mut self,
+        reader: &mut impl Read,
+        root: &Path,
+        relative: &Path,
+        mode: Option<u32>,
+    ) -> Result<()> {
+        if let Some(parent) = relative.parent() {
+            create_dirs(root, parent, &self.archive)?;
+        }
+        let path = &root.join(relative);
+        // never write through a symlink left at the path
+        let _ = std::fs::remove_file(path);
+        let mut file = File::create(path).context(CommonIoSnafu)?;
+        let remaining = self.extractor.max_total_size.saturating_sub(self.copied);
+        let written =
+            std::io::copy(&mut reader.take(remaining + 1), &mut file).context(CommonIoSnafu)?;
+        self.copied += written;
+        if written > remaining {
+            drop(file);
+            let _ = std::fs::remove_file(path);
+            return self.exceeded(format!("{} bytes", self.extractor.max_total_size));
+        }
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(
+                path,
+                std::fs::Permissions::from_mode(mode & PERMISSION_MASK),
+            )
+            .context(CommonIoSnafu)?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        Ok(())
+    }
+
+    fn exceeded<T>(&self, limit: String) -> Result<T> {
+        ArchiveLimitExceededSnafu {
+            archive: &self.archive,
+            limit,
+        }
+        .fail()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in entries {
+            let options = zip::write::FileOptions::default().unix_permissions(0o750);
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path, entries: &[(&str, &str)]) {
+        let encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            // `set_path` refuses `..`, write the raw name like a crafted archive
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o4755);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            tar.append(&header, content.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn extract_into_target_safely() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let target = dir.join("target");
+
+        let zip_path = dir.join("plugins.zip");
+        write_zip(
+            &zip_path,
+            &[("bin/agent", "binary"), ("./conf.toml", "a = 1")],
+        );
+        let files = ArchiveExtractor::new(&target).extract(&zip_path).unwrap();
+        assert_eq!(
+            files,
+            vec![target.join("bin/agent"), target.join("conf.toml")]
+        );
+        assert_eq!(
+            std::fs::read_to_string(target.join("bin/agent")).unwrap(),
+            "binary"
+        );
+
+        let tar_path = dir.join("plugins.tar.gz");
+        write_tar_gz(&tar_path, &[("tools/check", "#!/bin/sh")]);
+        let files = ArchiveExtractor::new(&target).extract(&tar_path).unwrap();
+        assert_eq!(files, vec![target.join("tools/check")]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = |path: &str| {
+                std::fs::metadata(target.join(path))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o7777
+            };
+            assert_eq!(mode("bin/agent"), 0o750);
+            assert_eq!(mode("tools/check"), 0o755);
+        }
+
+        write_zip(&zip_path, &[("ok.txt", "ok"), ("../escaped.txt", "evil")]);
+        assert!(ArchiveExtractor::new(&target).extract(&zip_path).is_err());
+        assert!(!target.join("ok.txt").exists());
+        assert!(!dir.join("escaped.txt").exists());
+        write_tar_gz(&tar_path, &[("/tmp/absolute.txt", "evil")]);
+        assert!(ArchiveExtractor::new(&target).extract(&tar_path).is_err());
+
+        write_zip(&zip_path, &[("a", "0123456789"), ("b", "0123456789")]);
+        let extractor = ArchiveExtractor::new(&target);
+        assert!(extractor
+            .clone()
+            .max_total_size(15)
+            .extract(&zip_path)
+            .is_err());
+        assert!(extractor.clone().max_entries(1).extract(&zip_path).is_err());
+        assert!(extractor.extract(dir.join("plugins.rar")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuse_symlink_in_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let (target, outside) = (dir.path().join("target"), dir.path().join("outside"));
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, target.join("link")).unwrap();
+
+        let zip_path = dir.path().join("plugins.zip");
+        write_zip(&zip_path, &[("ok.txt", "ok"), ("link/evil.txt", "evil")]);
+        assert!(ArchiveExtractor::new(&target).extract(&zip_path).is_err());
+        assert!(!target.join("ok.txt").exists());
+        assert!(!outside.join("evil.txt").exists());
+        assert!(!dir.path().join("target.extracting").exists());
+    }
+
+    #[test]
+    fn sanitize_entry_path() {
+        assert_eq!(entry_path("a/./b"), Some(PathBuf::from("a/b")));
+        assert_eq!(entry_path("./"), Some(PathBuf::new()));
+        assert_eq!(entry_path("a/../../b"), None);
+        assert_eq!(entry_path("/etc/passwd"), None);
+    }
+}
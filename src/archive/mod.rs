@@ -0,0 +1,6 @@
+pub use create::{ArchiveBuilder, ArchiveSummary};
+pub(crate) use extract::entry_path;
+pub use extract::{ArchiveExtractor, ArchiveFormat, DEFAULT_MAX_ENTRIES, DEFAULT_MAX_TOTAL_SIZE};
+
+mod create;
+mod extract;
@@ -0,0 +1,129 @@
+use std::io::{BufWriter, Write};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use snafu::ResultExt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+
+use crate::archive::{ArchiveBuilder, ArchiveFormat};
+use crate::error::{BlockingTaskSnafu, Result};
+
+pub const DIAGNOSTICS_PATH: &str = "/diagnostics.zip";
+pub const DIAGNOSTICS_TAR_GZ_PATH: &str = "/diagnostics.tar.gz";
+
+/// bytes of a body chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// `GET /diagnostics.zip` or `GET /diagnostics.tar.gz` by the format of `builder`
+/// the archive is streamed as it's packed for every request, see `ArchiveBuilder::write_stream`
+/// ```rust,no_run
+/// use axum::Router;
+/// use awesome_operates::archive::{ArchiveBuilder, ArchiveFormat};
+/// use awesome_operates::axum::diagnostics_router;
+///
+/// let builder = ArchiveBuilder::new(ArchiveFormat::Zip)
+///     .add_path("logs", "logs")
+///     .include("*.log*")
+///     .max_total_size(256 << 20);
+/// let app: Router = Router::new().merge(diagnostics_router(builder));
+/// ```
+pub fn diagnostics_router<S>(builder: ArchiveBuilder) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let path = match builder.format() {
+        ArchiveFormat::Zip => DIAGNOSTICS_PATH,
+        ArchiveFormat::TarGz => DIAGNOSTICS_TAR_GZ_PATH,
+    };
+    Router::new()
+        .route(path, get(get_diagnostics))
+        .with_state(builder)
+}
+
+pub async fn get_diagnostics(State(builder): State<ArchiveBuilder>) -> Result<Response> {
+    let format = builder.format();
+    let (sender, mut receiver) = mpsc::channel(4);
+    let task = tokio::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(CHUNK_SIZE, ChannelWriter(sender.clone()));
+        let result = builder.write_stream(writer);
+        if let Err(e) = &result {
+            tracing::warn!("diagnostics archive aborted: {e}");
+            // the client sees a broken body instead of a truncated archive
+            let _ = sender.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+        result
+    });
+    // failed before any bytes, like a bad glob, is responded as an error
+    let first = receiver.recv().await;
+    if !matches!(first, Some(Ok(_))) {
+        task.await.context(BlockingTaskSnafu)??;
+    }
+    let body = Body::from_stream(tokio_stream::iter(first).chain(ReceiverStream::new(receiver)));
+    let content_type = match format {
+        ArchiveFormat::Zip => "application/zip",
+        ArchiveFormat::TarGz => "application/gzip",
+    };
+    let filename = format!(
+        "diagnostics-{}.{}",
+        chrono::Local::now().format("%Y%m%d%H%M%S"),
+        format.extension()
+    );
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_owned()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// hand the bytes written on a blocking thread to the response body
+struct ChannelWriter(mpsc::Sender<std::io::Result<Vec<u8>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stream_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("agent.log"), "x".repeat(CHUNK_SIZE * 3)).unwrap();
+        let builder = ArchiveBuilder::new(ArchiveFormat::TarGz).add_path(dir.path(), "logs");
+        let response = get_diagnostics(State(builder.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, builder.to_bytes().unwrap().0);
+
+        let response = get_diagnostics(State(builder.include("[")))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,29 @@
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+
+use crate::schedule::{JobRegistry, JobStatus};
+
+pub const JOBS_PATH: &str = "/admin/jobs";
+
+/// `GET /admin/jobs` state, next run time and recent runs of every registered job
+/// ```rust,no_run
+/// use axum::Router;
+/// use awesome_operates::axum::jobs_router;
+/// use awesome_operates::schedule::JobRegistry;
+///
+/// let registry = JobRegistry::default();
+/// let app: Router = Router::new().merge(jobs_router(registry));
+/// ```
+pub fn jobs_router<S>(registry: JobRegistry) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(JOBS_PATH, get(jobs_status))
+        .with_state(registry)
+}
+
+pub async fn jobs_status(State(registry): State<JobRegistry>) -> Json<Vec<JobStatus>> {
+    Json(registry.statuses())
+}
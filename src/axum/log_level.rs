@@ -0,0 +1,64 @@
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::log::{log_directives, set_log_directives};
+
+pub const LOG_LEVEL_PATH: &str = "/admin/log-level";
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct LogLevel {
+    /// RUST_LOG syntax, like `info,awesome_operates::router=debug`
+    pub directives: String,
+}
+
+/// `GET /admin/log-level` show current directives
+/// `PUT /admin/log-level` with body `{"directives": "debug"}` change directives
+/// both are answered with `503` until the reload handle is installed
+/// ```rust,no_run
+/// use axum::Router;
+/// use awesome_operates::axum::log_level_router;
+///
+/// let app: Router = Router::new().merge(log_level_router());
+/// ```
+pub fn log_level_router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().route(LOG_LEVEL_PATH, get(get_log_level).put(put_log_level))
+}
+
+pub async fn get_log_level() -> Result<Json<LogLevel>> {
+    Ok(Json(LogLevel {
+        directives: log_directives()?,
+    }))
+}
+
+pub async fn put_log_level(Json(level): Json<LogLevel>) -> Result<Json<LogLevel>> {
+    set_log_directives(&level.directives)?;
+    get_log_level().await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use http::StatusCode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn invalid_directives_bad_request() {
+        let level = LogLevel {
+            directives: "info,[invalid".to_owned(),
+        };
+        let response = put_log_level(Json(level)).await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn uninitialised_service_unavailable() {
+        let response = get_log_level().await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
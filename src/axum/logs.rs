@@ -0,0 +1,89 @@
+use std::convert::Infallible;
+
+use axum::extract::{Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::get;
+use axum::{Json, Router};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::error::Result;
+use crate::log::{LogQuery, LogRecord, LogRingBuffer};
+
+pub const LOGS_PATH: &str = "/admin/logs";
+pub const LOGS_STREAM_PATH: &str = "/admin/logs/stream";
+
+/// `GET /admin/logs?level=info&target=awesome_operates&limit=100` recent logs as json
+/// `GET /admin/logs/stream?level=warn` new logs as Server-Sent Events
+/// an unknown `level` is answered with `400`
+/// ```rust,no_run
+/// use axum::Router;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use awesome_operates::axum::logs_router;
+/// use awesome_operates::log::LogRingBuffer;
+///
+/// let buffer = LogRingBuffer::new(1000);
+/// tracing::subscriber::set_global_default(tracing_subscriber::registry().with(buffer.clone())).unwrap();
+/// let app: Router = Router::new().merge(logs_router(buffer));
+/// ```
+pub fn logs_router<S>(buffer: LogRingBuffer) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(LOGS_PATH, get(logs_tail))
+        .route(LOGS_STREAM_PATH, get(logs_stream))
+        .with_state(buffer)
+}
+
+pub async fn logs_tail(
+    State(buffer): State<LogRingBuffer>,
+    Query(query): Query<LogQuery>,
+) -> Result<Json<Vec<LogRecord>>> {
+    query.validate()?;
+    Ok(Json(buffer.records(&query)))
+}
+
+pub async fn logs_stream(
+    State(buffer): State<LogRingBuffer>,
+    Query(query): Query<LogQuery>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    query.validate()?;
+    let stream = BroadcastStream::new(buffer.subscribe()).filter_map(move |record| {
+        // lagged receivers just skip the dropped records
+        let record = record.ok().filter(|record| query.matches(record))?;
+        Event::default().json_data(record).ok().map(Ok)
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use http::StatusCode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn unknown_level_bad_request() {
+        let buffer = LogRingBuffer::new(10);
+        let query = |level: &str| {
+            Query(LogQuery {
+                level: Some(level.to_owned()),
+                ..Default::default()
+            })
+        };
+        let response = logs_tail(State(buffer.clone()), query("verbose"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = logs_stream(State(buffer.clone()), query("loud"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = logs_tail(State(buffer), query("warn"))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
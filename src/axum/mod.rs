@@ -1,3 +1,15 @@
+mod diagnostics;
+mod jobs;
+mod log_level;
+mod logs;
 mod middlewares;
+mod version;
 
+pub use diagnostics::{
+    diagnostics_router, get_diagnostics, DIAGNOSTICS_PATH, DIAGNOSTICS_TAR_GZ_PATH,
+};
+pub use jobs::{jobs_router, jobs_status, JOBS_PATH};
+pub use log_level::{get_log_level, log_level_router, put_log_level, LogLevel, LOG_LEVEL_PATH};
+pub use logs::{logs_router, logs_stream, logs_tail, LOGS_PATH, LOGS_STREAM_PATH};
 pub use middlewares::query_trim_empty_items_middleware;
+pub use version::{get_build_info, version_router, VERSION_PATH};
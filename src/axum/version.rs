@@ -0,0 +1,27 @@
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+
+use crate::build::BuildInfo;
+
+pub const VERSION_PATH: &str = "/version";
+
+/// `GET /version` the `BuildInfo` as json
+/// ```rust,no_run
+/// use axum::Router;
+/// use awesome_operates::axum::version_router;
+///
+/// let app: Router = Router::new().merge(version_router(awesome_operates::build_info!()));
+/// ```
+pub fn version_router<S>(info: BuildInfo) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(VERSION_PATH, get(get_build_info))
+        .with_state(info)
+}
+
+pub async fn get_build_info(State(info): State<BuildInfo>) -> Json<BuildInfo> {
+    Json(info)
+}
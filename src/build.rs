@@ -1,3 +1,13 @@
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::error::{IncompatibleVersionSnafu, Result, VersionParseSnafu};
+use crate::helper::Version;
+
+/// value of build metadata can't be detected
+pub const UNKNOWN: &str = "unknown";
+
 /// create a `build.rs` at the same folder with `Cargo.toml`
 /// ```
 /// //build.rs
@@ -7,40 +17,239 @@
 /// ```
 /// then, you can use
 ///```
-///  use awesome_operates::build::program_about;
 ///  fn server() {
-///     println!("{}", program_about());
+///     println!("{}", awesome_operates::program_about!());
 /// }
 /// ```
 #[inline]
 pub fn build_init() {
-    println!(
-        "cargo:rustc-env=GIT_COMMIT={}",
-        build_data::get_git_commit().unwrap()
-    );
-    println!(
-        "cargo:rustc-env=GIT_BRANCH={}",
-        build_data::get_git_branch().unwrap()
-    );
-    println!(
-        "cargo:rustc-env=GIT_DIRTY={}",
-        build_data::get_git_dirty().unwrap()
-    );
-    println!("cargo:rustc-env=BUILD_DATETIME={}", chrono::Local::now());
+    let values = [
+        (
+            "GIT_COMMIT",
+            fallback("git commit", build_data::get_git_commit()),
+        ),
+        (
+            "GIT_BRANCH",
+            fallback("git branch", build_data::get_git_branch()),
+        ),
+        (
+            "GIT_DIRTY",
+            fallback(
+                "git dirty",
+                build_data::get_git_dirty().map(|dirty| dirty.to_string()),
+            ),
+        ),
+        ("BUILD_DATETIME", chrono::Local::now().to_string()),
+        ("BUILD_TARGET", fallback("target", env_var("TARGET"))),
+        ("BUILD_PROFILE", fallback("profile", env_var("PROFILE"))),
+        (
+            "BUILD_RUSTC_VERSION",
+            fallback("rustc version", build_data::get_rustc_version()),
+        ),
+        ("BUILD_FEATURES", enabled_features().join(",")),
+        (
+            "BUILD_HOSTNAME",
+            fallback("hostname", build_data::get_hostname()),
+        ),
+    ];
+    for (key, value) in values {
+        println!("cargo:rustc-env={key}={value}");
+    }
 }
 
+/// build from a source tarball has no git metadata, keep building with `unknown`
+fn fallback(name: &str, value: std::result::Result<String, String>) -> String {
+    value.unwrap_or_else(|e| {
+        println!("cargo:warning={name} unavailable, use `{UNKNOWN}`: {e}");
+        UNKNOWN.to_owned()
+    })
+}
+
+fn env_var(key: &str) -> std::result::Result<String, String> {
+    std::env::var(key).map_err(|e| format!("{key} {e}"))
+}
+
+/// cargo sets `CARGO_FEATURE_<NAME>` for build scripts, names are reported as cargo sets them
+fn enabled_features() -> Vec<String> {
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_owned))
+        .collect::<Vec<String>>();
+    features.sort();
+    features
+}
+
+/// build metadata of a program, reported to or by peers
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: String,
+    pub branch: String,
+    pub commit: String,
+    pub dirty: bool,
+    pub build_time: String,
+    pub rustc: String,
+    /// target triple, like `x86_64-unknown-linux-gnu`
+    pub target: String,
+    /// `debug` or `release`
+    pub profile: String,
+    /// enabled cargo features
+    pub features: Vec<String>,
+    pub hostname: String,
+}
+
+/// `BuildInfo` of the calling crate, its `build.rs` should call `build_init`
+/// ```rust
+/// let info = awesome_operates::build_info!();
+/// assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::build::BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            branch: option_env!("GIT_BRANCH").unwrap_or_default().to_owned(),
+            commit: option_env!("GIT_COMMIT").unwrap_or_default().to_owned(),
+            dirty: option_env!("GIT_DIRTY").is_some_and(|v| v.eq("true")),
+            build_time: option_env!("BUILD_DATETIME").unwrap_or_default().to_owned(),
+            rustc: option_env!("BUILD_RUSTC_VERSION")
+                .unwrap_or_default()
+                .to_owned(),
+            target: option_env!("BUILD_TARGET").unwrap_or_default().to_owned(),
+            profile: option_env!("BUILD_PROFILE").unwrap_or_default().to_owned(),
+            features: option_env!("BUILD_FEATURES")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(|feature| feature.to_owned())
+                .collect(),
+            hostname: option_env!("BUILD_HOSTNAME").unwrap_or_default().to_owned(),
+        }
+    };
+}
+
+/// `BuildInfo::about` of the calling crate as `&'static str`, rendered once and cached
+/// ```rust
+/// let about: &'static str = awesome_operates::program_about!();
+/// assert!(about.starts_with(&format!("version: {}", env!("CARGO_PKG_VERSION"))));
+/// ```
+#[macro_export]
+macro_rules! program_about {
+    () => {{
+        static ABOUT: ::std::sync::OnceLock<String> = ::std::sync::OnceLock::new();
+        ABOUT.get_or_init(|| $crate::build_info!().about()).as_str()
+    }};
+}
+
+/// the build metadata is read where this crate is compiled, not from the calling crate
+#[deprecated(note = "use `awesome_operates::program_about!()` to report the calling crate")]
 pub fn program_about() -> &'static str {
-    let values = [
-        ("version", option_env!("CARGO_PKG_VERSION")),
-        ("branch", option_env!("GIT_BRANCH")),
-        ("commit", option_env!("GIT_COMMIT")),
-        ("git dirty", option_env!("GIT_DIRTY")),
-        ("build datetime", option_env!("BUILD_DATETIME")),
-    ]
-    .iter()
-    .map(|(k, v)| format!("{k}: {}", v.unwrap_or_default()))
-    .collect::<Vec<String>>()
-    .join("\n");
-    let value = Box::new(values);
-    Box::leak(value)
+    crate::program_about!()
+}
+
+impl BuildInfo {
+    pub fn parsed_version(&self) -> Result<Version> {
+        Version::parse(&self.version)
+    }
+
+    /// one `key: value` per line
+    pub fn about(&self) -> String {
+        [
+            ("version", self.version.clone()),
+            ("branch", self.branch.clone()),
+            ("commit", self.commit.clone()),
+            ("git dirty", self.dirty.to_string()),
+            ("build datetime", self.build_time.clone()),
+            ("rustc", self.rustc.clone()),
+            ("target", self.target.clone()),
+            ("profile", self.profile.clone()),
+            ("features", self.features.join(",")),
+            ("build host", self.hostname.clone()),
+        ]
+        .iter()
+        .map(|(k, v)| format!("{k}: {v}"))
+        .collect::<Vec<String>>()
+        .join("\n")
+    }
+}
+
+/// allowed version ranges of peers, a peer is compatible when any range matches
+/// ```rust
+/// use awesome_operates::build::{BuildInfo, CompatibilityPolicy};
+///
+/// let policy = CompatibilityPolicy::new(&[">=1.4, <2", "^2.1"]).unwrap();
+/// let peer = BuildInfo {
+///     version: "1.5.0".to_owned(),
+///     ..Default::default()
+/// };
+/// assert!(policy.check(&peer).is_ok());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatibilityPolicy {
+    allowed: Vec<VersionReq>,
+}
+
+impl CompatibilityPolicy {
+    /// ranges like `^1.2`, `>=1.4, <2`, `~2.1.0`
+    pub fn new(ranges: &[&str]) -> Result<Self> {
+        let allowed = ranges
+            .iter()
+            .map(|range| VersionReq::parse(range).context(VersionParseSnafu { version: *range }))
+            .collect::<Result<Vec<VersionReq>>>()?;
+        Ok(Self { allowed })
+    }
+
+    /// every version is allowed when no ranges
+    pub fn allows(&self, version: &Version) -> bool {
+        self.allowed.is_empty()
+            || self
+                .allowed
+                .iter()
+                .any(|range| range.matches(version.as_semver()))
+    }
+
+    pub fn check(&self, peer: &BuildInfo) -> Result<()> {
+        let version = peer.parsed_version()?;
+        if !self.allows(&version) {
+            return IncompatibleVersionSnafu {
+                version: version.to_string(),
+                allowed: self
+                    .allowed
+                    .iter()
+                    .map(|range| range.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" || "),
+            }
+            .fail();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_compatibility() {
+        let policy = CompatibilityPolicy::new(&[">=1.4, <2", "^2.1"]).unwrap();
+        let peer = |version: &str| BuildInfo {
+            version: version.to_owned(),
+            ..Default::default()
+        };
+        assert!(policy.check(&peer("1.4.0")).is_ok());
+        assert!(policy.check(&peer("2.3.1")).is_ok());
+        assert!(policy.check(&peer("1.3.9")).is_err());
+        assert!(policy.check(&peer("2.0.5")).is_err());
+        assert!(policy.check(&peer("unknown")).is_err());
+        assert!(CompatibilityPolicy::new(&["not a range"]).is_err());
+        assert!(CompatibilityPolicy::default().check(&peer("0.0.1")).is_ok());
+    }
+
+    #[test]
+    fn git_unavailable_fallback() {
+        assert_eq!(
+            fallback("git commit", Err("not a git repository".to_owned())),
+            UNKNOWN
+        );
+        assert_eq!(fallback("git commit", Ok("abc123".to_owned())), "abc123");
+    }
 }
@@ -42,7 +42,7 @@ pub async fn pre_compress_dir(dir: &str) {
     tracing::info!("pre brotli compress for {dir} over");
 }
 
-/// only used for `pre_compress_dir`
+/// only used for `multi_compress` and `gzip_and_remove`
 #[macro_export]
 macro_rules! compress {
     ($encoder:ident, $extension:expr, $data:expr, $path:expr) => {
@@ -81,3 +81,24 @@ pub async fn multi_compress(path: &Path) -> Result<()> {
     compress!(GzipEncoder, "gz", data, path);
     Ok(())
 }
+
+/// gzip `path` into `path.gz` with the same encoder as `multi_compress`, then remove `path`
+pub async fn gzip_and_remove(path: &Path) -> Result<()> {
+    tracing::debug!("gzip compress {}", path.display());
+    let data = tokio::fs::read(path).await.context(CommonIoSnafu)?;
+    compress!(GzipEncoder, "gz", data, path);
+    tokio::fs::remove_file(path).await.context(CommonIoSnafu)?;
+    Ok(())
+}
+
+/// `gzip_and_remove` without a tokio runtime, the file is streamed instead of read at once
+pub fn sync_gzip_and_remove(path: &Path) -> Result<()> {
+    tracing::debug!("gzip compress {}", path.display());
+    let mut source = std::fs::File::open(path).context(CommonIoSnafu)?;
+    let target = std::fs::File::create(format!("{}.gz", path.display())).context(CommonIoSnafu)?;
+    let mut encoder = flate2::write::GzEncoder::new(target, flate2::Compression::best());
+    std::io::copy(&mut source, &mut encoder).context(CommonIoSnafu)?;
+    encoder.finish().context(CommonIoSnafu)?;
+    std::fs::remove_file(path).context(CommonIoSnafu)?;
+    Ok(())
+}
@@ -1,11 +1,14 @@
 use async_trait::async_trait;
 use rust_embed::RustEmbed;
-use snafu::ResultExt;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-use crate::error::{CommonIoSnafu, Result};
-use crate::helper;
+use crate::archive::{entry_path, ArchiveExtractor};
+use crate::error::{CommonIoSnafu, OptionNoneSnafu, Result, SerdeJsonSnafu};
 use crate::manage::binary_filepath_execute_success;
+use crate::{helper, verify};
 
 /// usage
 /// ```
@@ -32,17 +35,28 @@ pub trait AssetExtractExt: RustEmbed {
         Ok(())
     }
 
-    async fn perform_extract() -> Result<()> {
-        for file in Self::iter() {
-            tracing::debug!("extract {}", file.as_ref());
-            let filepath = file.as_ref();
-            if helper::is_current_running_newer(filepath).unwrap_or(true) {
-                let file = Self::get(filepath).unwrap().data.clone();
-                helper::write_filepath_with_data(filepath, file)?;
-            } else {
-                tracing::debug!("skip {filepath} because it newer");
-            }
-        }
+    /// embedded paths are relative to it, the current directory by default
+    /// only the embedded files are replaced on extracting, see `extract_assets`
+    fn extract_root() -> PathBuf {
+        PathBuf::new()
+    }
+
+    /// hashes of the extracted files
+    fn manifest_path() -> PathBuf {
+        Self::extract_root().join(format!(".{}{ASSET_MANIFEST_SUFFIX}", asset_name::<Self>()))
+    }
+
+    async fn perform_extract() -> Result<()>
+    where
+        Self: Sized,
+    {
+        let report = extract_assets::<Self>(&Self::extract_root(), &Self::manifest_path()).await?;
+        tracing::info!(
+            "extract assets, {} written, {} unchanged, {} removed",
+            report.written.len(),
+            report.skipped.len(),
+            report.removed.len()
+        );
         Ok(())
     }
 
@@ -51,18 +65,38 @@ pub trait AssetExtractExt: RustEmbed {
         vec![]
     }
 
+    /// files failed the signature verification are skipped, the verified bytes are installed
     async fn update_files() -> Result<()> {
         for (src, dst) in Self::update_filenames() {
             if Path::new(&src).exists() {
-                tokio::fs::rename(&src, &dst).await.context(CommonIoSnafu)?;
+                if let Err(e) = verify::install_update_file(&src, &dst).await {
+                    tracing::warn!("skip update file {src}: {e}");
+                }
             }
         }
         Ok(())
     }
 
+    /// an update file passed the signature verification and executed successfully
     async fn any_update_files_exists() -> bool {
-        for (src, _) in &Self::update_filenames() {
-            if binary_filepath_execute_success(src).await.is_ok_and(|v| v) {
+        for (src, dst) in &Self::update_filenames() {
+            if !Path::new(src).exists() {
+                continue;
+            }
+            let update = match verify::stage_update_file(src, dst).await {
+                Ok(update) => update,
+                Err(e) => {
+                    tracing::warn!("skip update file {src}: {e}");
+                    continue;
+                }
+            };
+            let executed = binary_filepath_execute_success(&update.path().to_string_lossy())
+                .await
+                .is_ok_and(|v| v);
+            if let Err(e) = update.discard().await {
+                tracing::warn!("remove staged update file of {src} failed `{e:?}`");
+            }
+            if executed {
                 tracing::info!("check filepath at `{src}` execute success");
                 return true;
             }
@@ -70,7 +104,10 @@ pub trait AssetExtractExt: RustEmbed {
         false
     }
 
-    async fn extract() -> Result<()> {
+    async fn extract() -> Result<()>
+    where
+        Self: Sized,
+    {
         Self::update_files().await?;
         Self::before_extract().await?;
         Self::perform_extract().await?;
@@ -83,6 +120,171 @@ pub trait AssetExtractExt: RustEmbed {
     }
 }
 
+pub const ASSET_MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// embedded path to the lowercase hex sha256 of the content
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct AssetManifest {
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExtractReport {
+    pub written: Vec<String>,
+    pub skipped: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// extract `A` under `root`, files not changed on disk are kept
+/// changed files are staged in `root` first, then renamed over the old ones one by one,
+/// nothing is touched when staging fails
+/// files of the previous manifest no longer embedded are removed, any other file is left alone
+/// ```rust
+/// use std::path::Path;
+///
+/// use awesome_operates::embed::extract_assets;
+///
+/// #[derive(rust_embed::RustEmbed)]
+/// #[folder = "src/assets"]
+/// struct Asset;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let dir = tempfile::tempdir().unwrap();
+/// std::env::set_current_dir(dir.path()).unwrap();
+/// std::fs::write("data.db", "kept").unwrap();
+/// // the current directory, embedded paths have no common top directory
+/// let report = extract_assets::<Asset>(Path::new(""), Path::new(".asset.manifest.json"))
+///     .await
+///     .unwrap();
+/// assert_eq!(report.written.len(), Asset::iter().count());
+/// assert!(Path::new("swagger/index.css").exists());
+/// assert_eq!(std::fs::read_to_string("data.db").unwrap(), "kept");
+/// # }
+/// ```
+pub async fn extract_assets<A: RustEmbed>(
+    root: &Path,
+    manifest_path: &Path,
+) -> Result<ExtractReport> {
+    let previous = match tokio::fs::read(manifest_path).await {
+        Ok(content) => serde_json::from_slice::<AssetManifest>(&content).unwrap_or_else(|e| {
+            tracing::warn!(
+                "ignore broken asset manifest {}: {e}",
+                manifest_path.display()
+            );
+            AssetManifest::default()
+        }),
+        Err(_) => AssetManifest::default(),
+    };
+    // the iterator isn't `Send`
+    let files = A::iter()
+        .map(|file| file.to_string())
+        .collect::<Vec<String>>();
+    let staging = root.join(format!(".{}.staging", asset_name::<A>()));
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+
+    let mut manifest = AssetManifest::default();
+    let mut report = ExtractReport::default();
+    let result = async {
+        stage_assets::<A>(&files, root, &staging, &mut manifest, &mut report).await?;
+        let content = serde_json::to_vec_pretty(&manifest).context(SerdeJsonSnafu)?;
+        let staged_manifest = staging.join(ASSET_MANIFEST_SUFFIX);
+        tokio::fs::write(&staged_manifest, content)
+            .await
+            .context(CommonIoSnafu)?;
+        swap_assets(root, &staging, &report.written).await?;
+        helper::create_file_parent_dir(manifest_path).await?;
+        tokio::fs::rename(&staged_manifest, manifest_path)
+            .await
+            .context(CommonIoSnafu)
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    result?;
+
+    for filepath in previous.files.keys() {
+        if manifest.files.contains_key(filepath) {
+            continue;
+        }
+        let Some(relative) = entry_path(filepath) else {
+            tracing::warn!("ignore unsafe path {filepath} in the asset manifest");
+            continue;
+        };
+        match tokio::fs::remove_file(root.join(relative)).await {
+            Ok(()) => {
+                tracing::debug!("remove {filepath} no longer embedded");
+                report.removed.push(filepath.clone());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("remove {filepath} no longer embedded failed `{e:?}`"),
+        }
+    }
+    Ok(report)
+}
+
+/// write the changed files into `staging`, the unchanged ones are only recorded
+async fn stage_assets<A: RustEmbed>(
+    files: &[String],
+    root: &Path,
+    staging: &Path,
+    manifest: &mut AssetManifest,
+    report: &mut ExtractReport,
+) -> Result<()> {
+    tokio::fs::create_dir_all(staging)
+        .await
+        .context(CommonIoSnafu)?;
+    for filepath in files {
+        let embedded = A::get(filepath).context(OptionNoneSnafu)?;
+        let hash = hex::encode(embedded.metadata.sha256_hash());
+        manifest.files.insert(filepath.clone(), hash.clone());
+        // hash on disk, the file may be changed since the last extraction
+        let unchanged = helper::sha256_file(root.join(filepath))
+            .await
+            .is_ok_and(|actual| actual.eq(&hash));
+        if unchanged {
+            tracing::debug!("keep unchanged {filepath}");
+            report.skipped.push(filepath.clone());
+            continue;
+        }
+        tracing::debug!("extract {filepath}");
+        let staged = staging.join(filepath);
+        helper::create_file_parent_dir(&staged).await?;
+        tokio::fs::write(&staged, &embedded.data)
+            .await
+            .context(CommonIoSnafu)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+                .await
+                .context(CommonIoSnafu)?;
+        }
+        report.written.push(filepath.clone());
+    }
+    Ok(())
+}
+
+/// rename the staged files over the old ones, then extract the written zips into `root`
+async fn swap_assets(root: &Path, staging: &Path, written: &[String]) -> Result<()> {
+    for filepath in written {
+        let target = root.join(filepath);
+        helper::create_file_parent_dir(&target).await?;
+        tokio::fs::rename(staging.join(filepath), &target)
+            .await
+            .context(CommonIoSnafu)?;
+    }
+    for filepath in written.iter().filter(|v| v.ends_with(".zip")) {
+        ArchiveExtractor::new(root).extract_zip(root.join(filepath))?;
+    }
+    Ok(())
+}
+
+/// like `awesome_operates-embed-Asset`
+fn asset_name<A: ?Sized>() -> String {
+    std::any::type_name::<A>().replace("::", "-")
+}
+
 #[derive(rust_embed::RustEmbed)]
 #[prefix = "embed_files/"]
 #[folder = "src/assets/"]
@@ -92,3 +294,76 @@ pub const EXTRACT_SWAGGER_DIR_PATH: &str = "embed_files/swagger";
 pub const EXTRACT_DIR_PATH: &str = "embed_files";
 
 impl AssetExtractExt for Asset {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "src/assets/"]
+    struct TestAsset;
+
+    #[tokio::test]
+    async fn extract_by_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("assets");
+        let manifest_path = root.join("assets.manifest.json");
+        let report = extract_assets::<TestAsset>(&root, &manifest_path)
+            .await
+            .unwrap();
+        assert_eq!(report.written.len(), TestAsset::iter().count());
+        assert!(root.join("swagger/index.css").exists());
+
+        tokio::fs::remove_file(root.join("404.html")).await.unwrap();
+        tokio::fs::write(root.join("swagger/index.css"), "changed")
+            .await
+            .unwrap();
+        let mut manifest: AssetManifest =
+            serde_json::from_slice(&tokio::fs::read(&manifest_path).await.unwrap()).unwrap();
+        manifest
+            .files
+            .insert("stale.txt".to_owned(), "0".repeat(64));
+        manifest
+            .files
+            .insert("../outside.txt".to_owned(), "0".repeat(64));
+        tokio::fs::write(&manifest_path, serde_json::to_vec(&manifest).unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(root.join("stale.txt"), "stale")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("outside.txt"), "kept")
+            .await
+            .unwrap();
+        // written at runtime, like `swagger-init.js` of `InitSwagger`
+        tokio::fs::write(root.join("swagger/swagger-init.js"), "runtime")
+            .await
+            .unwrap();
+
+        let report = extract_assets::<TestAsset>(&root, &manifest_path)
+            .await
+            .unwrap();
+        assert_eq!(report.written, vec!["404.html", "swagger/index.css"]);
+        assert_eq!(report.skipped.len(), TestAsset::iter().count() - 2);
+        assert_eq!(report.removed, vec!["stale.txt"]);
+        assert!(!root.join("stale.txt").exists());
+        assert!(dir.path().join("outside.txt").exists());
+        assert_ne!(
+            tokio::fs::read(root.join("swagger/index.css"))
+                .await
+                .unwrap(),
+            b"changed"
+        );
+        assert_eq!(
+            tokio::fs::read(root.join("swagger/swagger-init.js"))
+                .await
+                .unwrap(),
+            b"runtime"
+        );
+        assert!(manifest_path.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert!(!root
+            .join(".awesome_operates-embed-tests-TestAsset.staging")
+            .exists());
+    }
+}
@@ -85,6 +85,13 @@ pub enum AppError {
         location: Location,
     },
 
+    #[snafu(display("zip create {}", source))]
+    ArchiveCreate {
+        source: zip::result::ZipError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("log file build InitError {}", source))]
     LogFileBuild {
         source: tracing_appender::rolling::InitError,
@@ -99,6 +106,193 @@ pub enum AppError {
         location: Location,
     },
 
+    #[snafu(display("log filter directives parse error {source}"))]
+    LogFilterParse {
+        source: tracing_subscriber::filter::ParseError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("log level reload not initialised"))]
+    LogReloadUninitialized {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("log level `{level}` parse error {source}"))]
+    LogLevelParse {
+        level: String,
+        source: tracing::metadata::ParseLevelError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("log filter reload error {source}"))]
+    LogFilterReload {
+        source: tracing_subscriber::reload::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("cron expression `{expression}` parse error {source}"))]
+    CronParse {
+        expression: String,
+        source: cron::error::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("socket activation fd {fd} is neither a tcp nor a unix stream listener"))]
+    UnsupportedListenFd {
+        fd: i32,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("`{path}` exists and is not a socket, refuse to remove it"))]
+    NotSocket {
+        path: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("command `{command}` failed with {status}, stderr `{stderr}`"))]
+    CommandFailed {
+        command: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("sha256 of {filepath} is {actual}, expected {expected}"))]
+    ChecksumMismatch {
+        filepath: String,
+        expected: String,
+        actual: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("no backup {filepath} to roll back to"))]
+    BackupMissing {
+        filepath: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("ed25519 public key invalid {source}"))]
+    PublicKeyInvalid {
+        source: ed25519_dalek::SignatureError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("update public key is already set"))]
+    PublicKeyAlreadySet {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("no update public key configured to verify {filepath}"))]
+    UpdateUnsigned {
+        filepath: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("signature of {filepath} missing"))]
+    SignatureMissing {
+        filepath: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("signature of {filepath} invalid {source}"))]
+    SignatureInvalid {
+        filepath: String,
+        source: ed25519_dalek::SignatureError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("update request {url} error {source}"))]
+    UpdateRequest {
+        url: String,
+        source: reqwest::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("update request {url} responded {status}"))]
+    UpdateResponseStatus {
+        url: String,
+        status: reqwest::StatusCode,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("version `{version}` parse error {source}"))]
+    VersionParse {
+        version: String,
+        source: semver::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("no version found in `{text}`"))]
+    VersionNotFound {
+        text: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("peer version {version} is not in allowed ranges {allowed}"))]
+    IncompatibleVersion {
+        version: String,
+        allowed: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("unsupported archive {filepath}, expect `.zip`, `.tar.gz` or `.tgz`"))]
+    ArchiveUnsupported {
+        filepath: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("refuse entry `{entry}` of {archive} escapes the target directory"))]
+    ArchiveEntryUnsafe {
+        archive: String,
+        entry: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("archive {archive} exceeds the limit {limit}"))]
+    ArchiveLimitExceeded {
+        archive: String,
+        limit: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("glob `{pattern}` invalid {source}"))]
+    GlobPattern {
+        pattern: String,
+        source: globset::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("blocking task failed {source}"))]
+    BlockingTask {
+        source: tokio::task::JoinError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("serde urlencoded error {source}"))]
     SerdeUrlEncodedSer {
         source: serde_urlencoded::ser::Error,
@@ -127,10 +321,27 @@ pub enum AppError {
     },
 }
 
+impl AppError {
+    /// errors caused by the request are `400`, features not set up are `503`, others are `500`
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::LogFilterParse { .. } | AppError::LogLevelParse { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            AppError::LogReloadUninitialized { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let status_code = StatusCode::INTERNAL_SERVER_ERROR;
-        tracing::error!("error happened: {self:?}\n display error: {self}");
+        let status_code = self.status_code();
+        if status_code.is_server_error() {
+            tracing::error!("error happened: {self:?}\n display error: {self}");
+        } else {
+            tracing::warn!("bad request: {self}");
+        }
         (
             status_code,
             Json(serde_json::json!({
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing_appender::non_blocking::WorkerGuard;
+
+use super::{shutdown_signal, shutdown_token};
+use crate::schedule::wait_jobs_finished;
+
+/// hooks with lower priority run first, hooks with the same priority run concurrently
+pub mod priority {
+    pub const STOP_ACCEPTING: i32 = 0;
+    pub const DRAIN_HTTP: i32 = 100;
+    pub const STOP_SCHEDULERS: i32 = 200;
+    pub const DEFAULT: i32 = 500;
+    /// the last one, so logs from other hooks are written
+    pub const FLUSH_LOGS: i32 = 1000;
+}
+
+/// exit code used by `wait_signal_and_shutdown` when hooks overrun the deadline
+pub const SHUTDOWN_OVERRUN_EXIT_CODE: i32 = 1;
+
+/// hooks from `priority::FLUSH_LOGS` still run this long after the deadline exceeded
+pub const FLUSH_LOGS_GRACE: Duration = Duration::from_secs(5);
+
+type HookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Hook = Box<dyn FnOnce() -> HookFuture + Send>;
+type PriorityHooks = BTreeMap<i32, Vec<(String, Hook)>>;
+
+/// hand out cancellation tokens to subsystems and run cleanup hooks in order on shutdown
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use awesome_operates::graceful::{priority, ShutdownCoordinator};
+///
+/// # async fn run() {
+/// let guard = awesome_operates::log::tracing_both_file_stdout("logs", "", "agent.log", None)
+///     .await
+///     .unwrap();
+/// let coordinator = ShutdownCoordinator::new(Duration::from_secs(30));
+/// coordinator.register_log_guard(guard);
+/// coordinator.register_scheduled_jobs();
+///
+/// let token = coordinator.token();
+/// let server = tokio::spawn(async move {
+///     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+///     axum::serve(listener, axum::Router::new())
+///         .with_graceful_shutdown(token.cancelled_owned())
+///         .await
+///         .unwrap();
+/// });
+/// coordinator.register_hook("drain http", priority::DRAIN_HTTP, || async move {
+///     let _ = server.await;
+/// });
+/// // exit with non-zero code when hooks overrun 30 seconds
+/// coordinator.wait_signal_and_shutdown().await;
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    token: CancellationToken,
+    deadline: Duration,
+    hooks: Arc<Mutex<PriorityHooks>>,
+}
+
+impl ShutdownCoordinator {
+    /// tokens are children of `graceful::shutdown_token()`
+    pub fn new(deadline: Duration) -> Self {
+        Self::with_token(shutdown_token(), deadline)
+    }
+
+    pub fn with_token(token: CancellationToken, deadline: Duration) -> Self {
+        Self {
+            token,
+            deadline,
+            hooks: Default::default(),
+        }
+    }
+
+    /// cancelled when shutdown starts, before any hook runs
+    pub fn token(&self) -> CancellationToken {
+        self.token.child_token()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn register_hook<F, Fut>(&self, name: &str, priority: i32, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let hook: Hook = Box::new(move || Box::pin(hook()));
+        if let Ok(mut hooks) = self.hooks.lock() {
+            hooks
+                .entry(priority)
+                .or_default()
+                .push((name.to_owned(), hook));
+        }
+    }
+
+    /// drop the guard at last, flush the buffered logs into file
+    pub fn register_log_guard(&self, guard: WorkerGuard) {
+        self.register_hook("flush logs", priority::FLUSH_LOGS, || async move {
+            drop(guard);
+        });
+    }
+
+    /// cancel `graceful::shutdown_token()` and wait for in-flight runs of `crate::schedule` jobs,
+    /// the jobs stop even the coordinator is created `with_token`
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use awesome_operates::graceful::ShutdownCoordinator;
+    /// use awesome_operates::schedule::run_with_cron;
+    /// use tokio_util::sync::CancellationToken;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let coordinator =
+    ///     ShutdownCoordinator::with_token(CancellationToken::new(), Duration::from_secs(1));
+    /// coordinator.register_scheduled_jobs();
+    /// let handle = run_with_cron(|| async {}, "* * * * * *").unwrap();
+    /// assert!(coordinator.shutdown().await);
+    /// assert!(handle.next_run().is_none());
+    /// # }
+    /// ```
+    pub fn register_scheduled_jobs(&self) {
+        let deadline = self.deadline;
+        self.register_hook(
+            "stop schedulers",
+            priority::STOP_SCHEDULERS,
+            move || async move {
+                shutdown_token().cancel();
+                wait_jobs_finished(deadline).await;
+            },
+        );
+    }
+
+    /// cancel the tokens and run every hook, return `false` when the deadline exceeded
+    /// after the deadline exceeded, the remaining hooks are skipped
+    /// except the ones from `priority::FLUSH_LOGS`, they run within `FLUSH_LOGS_GRACE`
+    pub async fn shutdown(&self) -> bool {
+        tracing::info!("shutdown with deadline {:?}", self.deadline);
+        self.token.cancel();
+        let deadline = Instant::now() + self.deadline;
+        let hooks = self
+            .hooks
+            .lock()
+            .map(|mut hooks| std::mem::take(&mut *hooks))
+            .unwrap_or_default();
+        let mut overrun = false;
+        for (priority, group) in hooks {
+            let names = group
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<String>>();
+            let group_deadline = match (overrun, priority >= priority::FLUSH_LOGS) {
+                (false, _) => deadline,
+                (true, true) => Instant::now() + FLUSH_LOGS_GRACE,
+                (true, false) => {
+                    tracing::error!("skip shutdown hooks {names:?} after the deadline exceeded");
+                    continue;
+                }
+            };
+            tracing::info!("run shutdown hooks {names:?} with priority {priority}");
+            let mut tasks = tokio::task::JoinSet::new();
+            for (_, hook) in group {
+                tasks.spawn(hook());
+            }
+            let finished = tokio::time::timeout_at(group_deadline, async {
+                while tasks.join_next().await.is_some() {}
+            })
+            .await
+            .is_ok();
+            if !finished {
+                tracing::error!("shutdown hooks {names:?} overrun the deadline");
+                overrun = true;
+            }
+        }
+        if !overrun {
+            tracing::info!("shutdown hooks finished");
+        }
+        !overrun
+    }
+
+    /// wait for `shutdown_signal` then `shutdown`
+    /// exit the process with `SHUTDOWN_OVERRUN_EXIT_CODE` when the deadline exceeded
+    pub async fn wait_signal_and_shutdown(&self) {
+        tokio::select! {
+            _ = shutdown_signal() => {},
+            _ = self.token.cancelled() => {},
+        }
+        if !self.shutdown().await {
+            std::process::exit(SHUTDOWN_OVERRUN_EXIT_CODE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hooks_run_by_priority() {
+        let coordinator =
+            ShutdownCoordinator::with_token(CancellationToken::new(), Duration::from_secs(1));
+        let order = Arc::new(Mutex::new(vec![]));
+        for (name, priority) in [
+            ("logs", priority::FLUSH_LOGS),
+            ("http", priority::DRAIN_HTTP),
+        ] {
+            let order = order.clone();
+            coordinator.register_hook(name, priority, move || async move {
+                order.lock().unwrap().push(name);
+            });
+        }
+        let subsystem = coordinator.token();
+        assert!(coordinator.shutdown().await);
+        assert!(subsystem.is_cancelled());
+        assert_eq!(*order.lock().unwrap(), vec!["http", "logs"]);
+    }
+
+    #[tokio::test]
+    async fn hooks_overrun_deadline() {
+        let coordinator =
+            ShutdownCoordinator::with_token(CancellationToken::new(), Duration::from_millis(100));
+        coordinator.register_hook("slow", priority::DEFAULT, || {
+            tokio::time::sleep(Duration::from_secs(10))
+        });
+        let order = Arc::new(Mutex::new(vec![]));
+        for (name, priority) in [
+            ("scheduler", priority::STOP_SCHEDULERS + 500),
+            ("logs", priority::FLUSH_LOGS),
+        ] {
+            let order = order.clone();
+            coordinator.register_hook(name, priority, move || async move {
+                order.lock().unwrap().push(name);
+            });
+        }
+        assert!(!coordinator.shutdown().await);
+        // logs are flushed even the deadline exceeded
+        assert_eq!(*order.lock().unwrap(), vec!["logs"]);
+    }
+}
@@ -1,4 +1,22 @@
+use once_cell::sync::Lazy;
 use tokio::signal;
+use tokio_util::sync::CancellationToken;
+
+pub use coordinator::{priority, ShutdownCoordinator, SHUTDOWN_OVERRUN_EXIT_CODE};
+#[cfg(unix)]
+pub use signals::{DispatchSignal, SignalDispatcher};
+
+mod coordinator;
+#[cfg(unix)]
+mod signals;
+
+static SHUTDOWN_TOKEN: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+
+/// cancelled when `shutdown_signal` received a signal
+/// schedulers in `crate::schedule` stop starting new runs after it is cancelled
+pub fn shutdown_token() -> CancellationToken {
+    SHUTDOWN_TOKEN.clone()
+}
 
 /// used for axum
 /// ```
@@ -12,6 +30,8 @@ use tokio::signal;
 ///  //    .with_graceful_shutdown(awesome_operates::graceful::shutdown_signal())
 ///  //    .await
 ///  //     .unwrap();
+///  // then wait for the in-flight scheduled jobs
+///  // awesome_operates::schedule::wait_jobs_finished(std::time::Duration::from_secs(30)).await;
 /// }
 /// ```
 pub async fn shutdown_signal() {
@@ -38,4 +58,5 @@ pub async fn shutdown_signal() {
     }
 
     tracing::info!("signal received, starting graceful shutdown");
+    SHUTDOWN_TOKEN.cancel();
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use snafu::ResultExt;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::shutdown_token;
+use crate::error::{CommonIoSnafu, Result};
+
+/// signals routed by `SignalDispatcher`, Ctrl+C and SIGTERM are left to `shutdown_signal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DispatchSignal {
+    /// SIGHUP, reload configuration by convention
+    Hangup,
+    /// SIGUSR1, dump state by convention
+    User1,
+    /// SIGUSR2
+    User2,
+    /// SIGQUIT
+    Quit,
+}
+
+impl DispatchSignal {
+    pub const ALL: [DispatchSignal; 4] = [Self::Hangup, Self::User1, Self::User2, Self::Quit];
+
+    pub fn kind(&self) -> SignalKind {
+        match self {
+            Self::Hangup => SignalKind::hangup(),
+            Self::User1 => SignalKind::user_defined1(),
+            Self::User2 => SignalKind::user_defined2(),
+            Self::Quit => SignalKind::quit(),
+        }
+    }
+}
+
+type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Handler = Arc<dyn Fn() -> HandlerFuture + Send + Sync>;
+
+/// route SIGHUP/SIGUSR1/SIGUSR2/SIGQUIT to registered async handlers
+/// only the signals with handlers registered before `spawn` are listened,
+/// others keep the default behavior
+/// ```rust,no_run
+/// use awesome_operates::graceful::{shutdown_signal, DispatchSignal, SignalDispatcher};
+///
+/// async fn reload_config() {}
+///
+/// # async {
+/// let dispatcher = SignalDispatcher::default();
+/// dispatcher.on(DispatchSignal::Hangup, reload_config);
+/// dispatcher.on(DispatchSignal::User1, || async { tracing::info!("dump state") });
+/// dispatcher.spawn().unwrap();
+/// shutdown_signal().await;
+/// # };
+/// ```
+#[derive(Clone, Default)]
+pub struct SignalDispatcher {
+    handlers: Arc<Mutex<HashMap<DispatchSignal, Vec<Handler>>>>,
+}
+
+impl SignalDispatcher {
+    pub fn on<F, Fut>(&self, signal: DispatchSignal, handler: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move || Box::pin(handler()));
+        if let Ok(mut handlers) = self.handlers.lock() {
+            handlers.entry(signal).or_default().push(handler);
+        }
+    }
+
+    /// run every handler of `signal` concurrently and wait them
+    pub async fn dispatch(&self, signal: DispatchSignal) {
+        let handlers = self
+            .handlers
+            .lock()
+            .map(|handlers| handlers.get(&signal).cloned().unwrap_or_default())
+            .unwrap_or_default();
+        tracing::info!("dispatch {signal:?} to {} handlers", handlers.len());
+        let mut tasks = tokio::task::JoinSet::new();
+        for handler in handlers {
+            tasks.spawn(handler());
+        }
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result {
+                tracing::error!("{signal:?} handler failed with `{e}`");
+            }
+        }
+    }
+
+    /// listen until `graceful::shutdown_token()` is cancelled
+    pub fn spawn(&self) -> Result<JoinHandle<()>> {
+        self.spawn_with_shutdown(shutdown_token())
+    }
+
+    pub fn spawn_with_shutdown(&self, shutdown: CancellationToken) -> Result<JoinHandle<()>> {
+        let registered = self
+            .handlers
+            .lock()
+            .map(|handlers| handlers.keys().copied().collect::<Vec<DispatchSignal>>())
+            .unwrap_or_default();
+        let mut streams = vec![];
+        for dispatch_signal in DispatchSignal::ALL {
+            if registered.contains(&dispatch_signal) {
+                streams.push((
+                    dispatch_signal,
+                    signal(dispatch_signal.kind()).context(CommonIoSnafu)?,
+                ));
+            }
+        }
+        let dispatcher = self.clone();
+        Ok(tokio::spawn(async move {
+            let mut tasks = tokio::task::JoinSet::new();
+            for (dispatch_signal, stream) in streams {
+                let dispatcher = dispatcher.clone();
+                let shutdown = shutdown.clone();
+                tasks.spawn(listen(dispatcher, dispatch_signal, stream, shutdown));
+            }
+            while tasks.join_next().await.is_some() {}
+            tracing::debug!("signal dispatcher stopped");
+        }))
+    }
+}
+
+async fn listen(
+    dispatcher: SignalDispatcher,
+    dispatch_signal: DispatchSignal,
+    mut stream: Signal,
+    shutdown: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            received = stream.recv() => {
+                if received.is_none() {
+                    break;
+                }
+                tracing::info!("signal {dispatch_signal:?} received");
+                dispatcher.dispatch(dispatch_signal).await;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dispatch_user_signal() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let dispatcher = SignalDispatcher::default();
+        let counter = count.clone();
+        dispatcher.on(DispatchSignal::User2, move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let shutdown = CancellationToken::new();
+        let handle = dispatcher.spawn_with_shutdown(shutdown.clone()).unwrap();
+        tokio::process::Command::new("kill")
+            .args(["-USR2", &std::process::id().to_string()])
+            .status()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        dispatcher.dispatch(DispatchSignal::User2).await;
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        shutdown.cancel();
+        handle.await.unwrap();
+    }
+}
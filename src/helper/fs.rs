@@ -1,5 +1,7 @@
+use sha2::{Digest, Sha256};
 use snafu::ResultExt;
 use std::path::Path;
+use tokio::io::AsyncReadExt;
 
 use crate::error::{CommonIoSnafu, Result};
 
@@ -11,3 +13,18 @@ pub async fn create_file_parent_dir(path: impl AsRef<Path>) -> Result<()> {
     }
     Ok(())
 }
+
+/// lowercase hex sha256 of the file content
+pub async fn sha256_file(path: impl AsRef<Path>) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await.context(CommonIoSnafu)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let size = file.read(&mut buf).await.context(CommonIoSnafu)?;
+        if size == 0 {
+            break;
+        }
+        hasher.update(&buf[..size]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use cfg_if::cfg_if;
 use snafu::ResultExt;
@@ -12,12 +12,15 @@ pub use format::{
     decimal_with_four, decimal_with_two, default_formatted_now, format_from_timestamp,
     formatted_now, human_bytes,
 };
-pub use fs::create_file_parent_dir;
+pub use fs::{create_file_parent_dir, sha256_file};
 pub use iter::iter_object;
 pub use network::{get_interface_ips, get_virtual_interfaces, sync_get_virtual_interfaces};
-pub use version::{calculate_agent_version, get_binary_file_version, get_pkg_version};
+pub use version::{
+    calculate_agent_version, get_binary_file_version, get_binary_version, get_pkg_version, Version,
+};
 
-use crate::error::{CommonIoSnafu, Result, ZipExtractSnafu};
+use crate::archive::ArchiveExtractor;
+use crate::error::{CommonIoSnafu, Result};
 
 mod execute;
 mod format;
@@ -53,8 +56,19 @@ pub fn get_program_args(excludes: &Vec<&str>) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
-/// one can
+/// write `file` at `filepath` and add the execute permission,
+/// a `.zip` is extracted into the current directory, see `write_filepath_with_data_into`
 pub fn write_filepath_with_data(filepath: impl AsRef<Path>, file: impl AsRef<[u8]>) -> Result<()> {
+    write_filepath_with_data_into(filepath, file, "")
+}
+
+/// `write_filepath_with_data` with a `.zip` extracted into `extract_target`
+/// by `archive::ArchiveExtractor`, like the parent directory of `filepath`
+pub fn write_filepath_with_data_into(
+    filepath: impl AsRef<Path>,
+    file: impl AsRef<[u8]>,
+    extract_target: impl AsRef<Path>,
+) -> Result<()> {
     if let Some(parent) = filepath.as_ref().parent() {
         std::fs::create_dir_all(parent).context(CommonIoSnafu)?;
     }
@@ -66,8 +80,7 @@ pub fn write_filepath_with_data(filepath: impl AsRef<Path>, file: impl AsRef<[u8
         try_rewrite(&filepath, &file)?;
     }
     if filepath.as_ref().extension().is_some_and(|v| v.eq("zip")) {
-        zip_extensions::zip_extract(&filepath.as_ref().to_path_buf(), &PathBuf::new())
-            .context(ZipExtractSnafu)?;
+        ArchiveExtractor::new(extract_target).extract_zip(&filepath)?;
     }
     cfg_if! {
         if #[cfg(unix)] {
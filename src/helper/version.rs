@@ -1,22 +1,204 @@
-use regex::{Captures, Regex};
-use snafu::{IntoError, NoneError, ResultExt};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use snafu::{IntoError, NoneError, OptionExt, ResultExt};
 use tokio::process::Command;
 
-use crate::error::{BinaryCannotBeExecuteSnafu, CommonIoSnafu, Result};
+use crate::error::{
+    BinaryCannotBeExecuteSnafu, CommonIoSnafu, Result, VersionNotFoundSnafu, VersionParseSnafu,
+};
 
 use super::show_bytes;
 
+static VERSION_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\d+\.\d+\.\d+(?:-[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?(?:\+[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?")
+        .unwrap()
+});
+
+/// numeric core only, for versions semver rejects like `1.02.3`
+static CORE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\d+)\.(\d+)\.(\d+)").unwrap());
+
+/// extensions stripped before extracting a version from file names
+const FILE_EXTENSIONS: [&str; 5] = [".exe", ".tar.gz", ".tgz", ".zip", ".gz"];
+
+/// os and arch parts of file names, not pre-release identifiers, like `agent-1.2.3-linux-amd64`
+const PLATFORM_SUFFIXES: [&str; 16] = [
+    "linux", "windows", "win", "darwin", "macos", "apple", "freebsd", "unknown", "amd64", "x86",
+    "i686", "i386", "aarch64", "arm64", "armv7", "musl",
+];
+
+/// SemVer 2.0 version, ordered with pre-release, build metadata is ignored when comparing
+/// ```rust
+/// use awesome_operates::helper::Version;
+///
+/// let version = Version::from_filename("agent-10.22.33-rc.1.exe").unwrap();
+/// assert_eq!(version.to_string(), "10.22.33-rc.1");
+/// assert!(version < "10.22.33".parse().unwrap());
+/// assert_eq!(version.packed(), Some(10 << 16 | 22 << 8 | 33));
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Version(semver::Version);
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self(semver::Version::new(major, minor, patch))
+    }
+
+    pub fn parse(version: &str) -> Result<Self> {
+        let trimmed = version.trim();
+        let trimmed = trimmed.strip_prefix(['v', 'V']).unwrap_or(trimmed);
+        semver::Version::parse(trimmed)
+            .map(Self)
+            .context(VersionParseSnafu { version })
+    }
+
+    /// the first version in text, like the `--version` output `agent 1.2.3 (abc123)`
+    pub fn extract(text: &str) -> Result<Self> {
+        let found = VERSION_REGEX
+            .find(text)
+            .context(VersionNotFoundSnafu { text })?;
+        Self::parse(found.as_str())
+    }
+
+    /// like `agent-10.22.33.exe`, `agent-1.2.3-rc.1.tar.gz`
+    /// a platform suffix is not a pre-release, `agent-1.2.3-rc.1-linux-amd64` is `1.2.3-rc.1`
+    pub fn from_filename(filename: &str) -> Result<Self> {
+        let stem = FILE_EXTENSIONS
+            .iter()
+            .find_map(|extension| filename.strip_suffix(extension))
+            .unwrap_or(filename);
+        let mut version = Self::extract(stem)?;
+        let pre = version.pre();
+        let kept = pre
+            .split('-')
+            .take_while(|part| {
+                let first = part.split('.').next().unwrap_or_default();
+                !PLATFORM_SUFFIXES
+                    .iter()
+                    .any(|platform| first.eq_ignore_ascii_case(platform))
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+        if kept.len() != pre.len() {
+            version.0.pre =
+                semver::Prerelease::new(&kept).context(VersionParseSnafu { version: filename })?;
+        }
+        Ok(version)
+    }
+
+    pub fn major(&self) -> u64 {
+        self.0.major
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.0.minor
+    }
+
+    pub fn patch(&self) -> u64 {
+        self.0.patch
+    }
+
+    /// empty for release versions
+    pub fn pre(&self) -> &str {
+        self.0.pre.as_str()
+    }
+
+    pub fn build(&self) -> &str {
+        self.0.build.as_str()
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.0.pre.is_empty()
+    }
+
+    /// `major << 16 | minor << 8 | patch`, `None` when a component is above 255
+    /// pre-release and build metadata are dropped
+    pub fn packed(&self) -> Option<u32> {
+        let component = |value: u64| u8::try_from(value).ok().map(u32::from);
+        Some(
+            component(self.0.major)? << 16
+                | component(self.0.minor)? << 8
+                | component(self.0.patch)?,
+        )
+    }
+
+    pub fn as_semver(&self) -> &semver::Version {
+        &self.0
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.major.hash(state);
+        self.0.minor.hash(state);
+        self.0.patch.hash(state);
+        self.0.pre.hash(state);
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_precedence(&other.0)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Version {
+    type Err = crate::error::AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl From<semver::Version> for Version {
+    fn from(version: semver::Version) -> Self {
+        Self(version)
+    }
+}
+
 pub fn get_pkg_version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
 
+/// packed version from text like `agent-10.22.33.exe`
+/// `0` when no version found or a component doesn't fit in a byte, see `Version::packed`
+/// leading zeros semver rejects, like `1.02.3`, are still read as numbers
 pub fn calculate_agent_version(version: &str) -> u32 {
-    let re = Regex::new(r"(?P<major>\d+)\.(?P<minor>\d+)\.(?P<patch>\d+)").unwrap();
-    let value = |x: &Captures, field: &str| x.name(field).unwrap().as_str().parse::<u8>().unwrap();
-    let (major, minor, patch) = re.captures(version).map_or((0, 0, 0), |c| {
-        (value(&c, "major"), value(&c, "minor"), value(&c, "patch"))
-    });
-    (major as u32) << 16 | (minor as u32) << 8 | patch as u32
+    Version::from_filename(version)
+        .ok()
+        .or_else(|| {
+            let captures = CORE_REGEX.captures(version)?;
+            let component = |index: usize| captures[index].parse::<u64>().ok();
+            Some(Version::new(component(1)?, component(2)?, component(3)?))
+        })
+        .and_then(|version| version.packed())
+        .unwrap_or_default()
 }
 
 pub async fn get_binary_file_version(exe_filepath: &str) -> Result<String> {
@@ -33,3 +215,61 @@ pub async fn get_binary_file_version(exe_filepath: &str) -> Result<String> {
     }
     Ok(show_bytes(output.stdout))
 }
+
+/// parsed from the `--version` output of the binary
+pub async fn get_binary_version(exe_filepath: &str) -> Result<Version> {
+    Version::extract(&get_binary_file_version(exe_filepath).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_order() {
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-beta",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ]
+        .map(|v| Version::parse(v).unwrap());
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        let build = Version::parse("v1.2.3+build.5").unwrap();
+        assert_eq!(build.build(), "build.5");
+        assert_eq!(build, Version::new(1, 2, 3));
+        assert!(Version::parse("1.2").is_err());
+        assert_eq!(
+            Version::extract("agent 2.10.300 (main abc123)").unwrap(),
+            Version::new(2, 10, 300)
+        );
+        assert!(Version::extract("agent").is_err());
+        assert_eq!(
+            Version::new(2, 10, 255).packed(),
+            Some(2 << 16 | 10 << 8 | 255)
+        );
+        assert_eq!(Version::new(2, 10, 300).packed(), None);
+        assert_eq!(Version::new(256, 0, 0).packed(), None);
+        assert_eq!(calculate_agent_version("agent-2.10.300"), 0);
+        assert_eq!(
+            Version::from_filename("agent-1.2.3-rc.1.tar.gz")
+                .unwrap()
+                .pre(),
+            "rc.1"
+        );
+        let platform = Version::from_filename("agent-1.2.3-linux-amd64").unwrap();
+        assert!(!platform.is_prerelease());
+        assert_eq!(platform, Version::new(1, 2, 3));
+        assert_eq!(
+            Version::from_filename("agent-1.2.3-rc.1-Linux-x86_64.tar.gz")
+                .unwrap()
+                .pre(),
+            "rc.1"
+        );
+        assert_eq!(
+            calculate_agent_version("agent-1.02.3"),
+            1 << 16 | 2 << 8 | 3
+        );
+    }
+}
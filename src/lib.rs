@@ -1,3 +1,4 @@
+pub mod archive;
 pub mod axum;
 pub mod build;
 pub mod compress;
@@ -12,3 +13,4 @@ pub mod router;
 pub mod schedule;
 pub mod server;
 pub mod swagger;
+pub mod verify;
@@ -0,0 +1,256 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+use snafu::{OptionExt, ResultExt};
+use tracing::Subscriber;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, reload, EnvFilter};
+
+pub use ring::{LogQuery, LogRecord, LogRingBuffer};
+pub use rolling::{cleanup_rotated_files, LogRetention, RollingWriter};
+
+use crate::error::{
+    CommonIoSnafu, LogFileBuildSnafu, LogFilterParseSnafu, LogFilterReloadSnafu,
+    LogReloadUninitializedSnafu, Result, TracingSetGlobalSnafu,
+};
+
+mod ring;
+mod rolling;
+
+/// directives used when `RUST_LOG` is not set, keep every event as before
+pub const DEFAULT_LOG_DIRECTIVES: &str = "trace";
+
+static LOG_FILTER_HANDLE: OnceCell<LogFilterHandle> = OnceCell::new();
+
+/// erase the subscriber type of `reload::Handle` so the handle can be kept globally
+trait ReloadFilter: Send + Sync {
+    fn reload(&self, filter: EnvFilter) -> Result<()>;
+
+    fn directives(&self) -> Result<String>;
+}
+
+impl<S: 'static> ReloadFilter for reload::Handle<EnvFilter, S> {
+    fn reload(&self, filter: EnvFilter) -> Result<()> {
+        reload::Handle::reload(self, filter).context(LogFilterReloadSnafu)
+    }
+
+    fn directives(&self) -> Result<String> {
+        self.with_current(|filter| filter.to_string())
+            .context(LogFilterReloadSnafu)
+    }
+}
+
+/// reload handle of the filter built by `file_stdout_layer`
+#[derive(Clone)]
+pub struct LogFilterHandle(Arc<dyn ReloadFilter>);
+
+impl LogFilterHandle {
+    pub fn directives(&self) -> Result<String> {
+        self.0.directives()
+    }
+
+    /// RUST_LOG syntax
+    pub fn set_directives(&self, directives: &str) -> Result<()> {
+        let filter = EnvFilter::try_new(directives).context(LogFilterParseSnafu)?;
+        self.0.reload(filter)
+    }
+
+    /// make it the one controlled by `set_log_directives`, only the first call takes effect
+    pub fn install_global(&self) -> bool {
+        LOG_FILTER_HANDLE.set(self.clone()).is_ok()
+    }
+}
+
+/// build `EnvFilter` from `RUST_LOG`, fallback to [`DEFAULT_LOG_DIRECTIVES`]
+pub fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_DIRECTIVES))
+}
+
+/// current filter directives, like `info,awesome_operates::router=debug`
+pub fn log_directives() -> Result<String> {
+    LOG_FILTER_HANDLE
+        .get()
+        .context(LogReloadUninitializedSnafu)?
+        .directives()
+}
+
+/// change filter directives at runtime, RUST_LOG syntax
+/// ```rust
+/// use awesome_operates::log::set_log_directives;
+///
+/// // fail because `tracing_both_file_stdout` is not called
+/// assert!(set_log_directives("info,awesome_operates::router=debug").is_err());
+/// ```
+pub fn set_log_directives(directives: &str) -> Result<()> {
+    EnvFilter::try_new(directives).context(LogFilterParseSnafu)?;
+    LOG_FILTER_HANDLE
+        .get()
+        .context(LogReloadUninitializedSnafu)?
+        .set_directives(directives)?;
+    tracing::info!("log directives changed into `{directives}`");
+    Ok(())
+}
+
+/// Usage
+/// ```rust
+/// use awesome_operates::log::tracing_both_file_stdout;
+/// use std::time::Duration;
+///
+///  async fn server() {
+///     // this is very important, must receive worker_guard value
+///     // or file writer will take no reflect
+///     let _guard = tracing_both_file_stdout("target", "", "agent.log", None).await;
+///     // let _guard = tracing_both_file_stdout("logs", "", "agent.log").await;
+///     tokio::time::sleep(Duration::from_secs(1)).await;
+///     tokio::spawn(async {
+///         tracing::info!("中文 abcd 1234 ?wajkasd[]{{}}【】---------");
+///     });
+///  }
+/// ```
+/// log level is controlled by `RUST_LOG`, and can be changed by `set_log_directives` at runtime
+pub async fn tracing_both_file_stdout(
+    log_dir: impl AsRef<Path>,
+    log_file_prefix: impl Into<String>,
+    log_file_suffix: impl Into<String>,
+    rotation: Option<Rotation>,
+) -> Result<WorkerGuard> {
+    let (layer, handle, guard) =
+        tracing_file_stdout_layer(log_dir, log_file_prefix, log_file_suffix, rotation).await?;
+    let collector = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::set_global_default(collector).context(TracingSetGlobalSnafu)?;
+    handle.install_global();
+    Ok(guard)
+}
+
+/// same as `tracing_both_file_stdout`, but return the layer without `set_global_default`,
+/// so it can be composed with other layers or used by `tracing::subscriber::with_default`
+/// call `LogFilterHandle::install_global` to control it by `set_log_directives`
+/// ```rust
+/// use awesome_operates::log::tracing_file_stdout_layer;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// async fn compose() {
+///     let (layer, _handle, _guard) = tracing_file_stdout_layer("target", "", "agent.log", None)
+///         .await
+///         .unwrap();
+///     let subscriber = tracing_subscriber::registry().with(layer);
+///     tracing::subscriber::with_default(subscriber, || tracing::info!("only here"));
+/// }
+/// ```
+pub async fn tracing_file_stdout_layer<S>(
+    log_dir: impl AsRef<Path>,
+    log_file_prefix: impl Into<String>,
+    log_file_suffix: impl Into<String>,
+    rotation: Option<Rotation>,
+) -> Result<(impl Layer<S> + Send + Sync, LogFilterHandle, WorkerGuard)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let (non_blocking, guard) =
+        tracing_with_file(log_dir, log_file_prefix, log_file_suffix, rotation).await?;
+    let (layer, handle) = file_stdout_layer(non_blocking);
+    Ok((layer, handle, guard))
+}
+
+/// stdout and `writer` layers filtered by a reloadable `EnvFilter`
+/// the filter only applies to these layers, it is changed by the returned handle
+pub fn file_stdout_layer<S>(writer: NonBlocking) -> (impl Layer<S> + Send + Sync, LogFilterHandle)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    #[cfg(windows)]
+    let ansi_enabled = false;
+    #[cfg(unix)]
+    let ansi_enabled = true;
+
+    let (filter, handle) = reload::Layer::new(env_filter());
+    let layer = fmt::Layer::new()
+        .with_ansi(ansi_enabled)
+        .with_writer(std::io::stdout)
+        .and_then(fmt::Layer::new().with_ansi(false).with_writer(writer))
+        .with_filter(filter);
+    (layer, LogFilterHandle(Arc::new(handle)))
+}
+
+pub async fn tracing_with_file(
+    log_dir: impl AsRef<Path>,
+    log_file_prefix: impl Into<String>,
+    log_file_suffix: impl Into<String>,
+    rotation: Option<Rotation>,
+) -> Result<(NonBlocking, WorkerGuard)> {
+    tokio::fs::create_dir_all(&log_dir)
+        .await
+        .context(CommonIoSnafu)?;
+    let file_appender = RollingFileAppender::builder()
+        .rotation(rotation.unwrap_or(Rotation::DAILY))
+        .filename_prefix(log_file_prefix)
+        .filename_suffix(log_file_suffix)
+        .build(log_dir)
+        .context(LogFileBuildSnafu)?;
+    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+    Ok((non_blocking, guard))
+}
+
+/// like `tracing_with_file`, but rotate by size as well and clean old files by `retention`
+/// the active file is `{log_file_prefix}.{log_file_suffix}`
+/// ```rust
+/// use awesome_operates::log::{tracing_with_retention, LogRetention};
+///
+/// async fn writer() {
+///     let retention = LogRetention {
+///         max_file_size: Some(10 * 1024 * 1024),
+///         max_files: Some(5),
+///         compress: true,
+///         ..Default::default()
+///     };
+///     let (_non_blocking, _guard) = tracing_with_retention("target", "agent", "log", retention)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn tracing_with_retention(
+    log_dir: impl AsRef<Path>,
+    log_file_prefix: impl Into<String>,
+    log_file_suffix: impl Into<String>,
+    retention: LogRetention,
+) -> Result<(NonBlocking, WorkerGuard)> {
+    let filename = [log_file_prefix.into(), log_file_suffix.into()]
+        .into_iter()
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<String>>()
+        .join(".");
+    let writer = RollingWriter::new(log_dir, filename, retention).context(CommonIoSnafu)?;
+    let (non_blocking, guard) = tracing_appender::non_blocking(writer);
+    Ok((non_blocking, guard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn layer_without_global_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let (layer, handle, guard) =
+            tracing_file_stdout_layer(dir.path(), "", "agent.log", Some(Rotation::NEVER))
+                .await
+                .unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("layer composed");
+            handle.set_directives("warn").unwrap();
+            assert_eq!(handle.directives().unwrap(), "warn");
+            tracing::info!("filtered out");
+        });
+        // nothing installed globally by building a layer
+        assert!(log_directives().is_err());
+        drop(guard);
+        let content = std::fs::read_to_string(dir.path().join("agent.log")).unwrap();
+        assert!(content.contains("layer composed"));
+        assert!(!content.contains("filtered out"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::error::{LogLevelParseSnafu, Result};
+use crate::helper::default_formatted_now;
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct LogRecord {
+    pub time: String,
+    /// `TRACE`, `DEBUG`, `INFO`, `WARN` or `ERROR`
+    pub level: String,
+    pub target: String,
+    /// the message and other fields as `key=value`
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+pub struct LogQuery {
+    /// the most verbose level included, `info` includes `info`, `warn` and `error`
+    pub level: Option<String>,
+    /// target prefix, like `awesome_operates::router`
+    pub target: Option<String>,
+    /// only the latest `limit` records
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// refuse an unknown `level` rather than matching everything
+    pub fn validate(&self) -> Result<()> {
+        if let Some(level) = &self.level {
+            Level::from_str(level).context(LogLevelParseSnafu { level })?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        let level_match = match self.level.as_deref().map(Level::from_str) {
+            Some(Ok(max)) => Level::from_str(&record.level).is_ok_and(|level| level <= max),
+            _ => true,
+        };
+        level_match
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|target| record.target.starts_with(target))
+    }
+}
+
+/// keep the last `capacity` events in memory, also broadcast every new event
+/// ```rust
+/// use awesome_operates::log::LogRingBuffer;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let buffer = LogRingBuffer::new(1000);
+/// let subscriber = tracing_subscriber::registry().with(buffer.clone());
+/// tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+/// assert_eq!(buffer.records(&Default::default())[0].message, "hello");
+/// ```
+#[derive(Clone)]
+pub struct LogRingBuffer {
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+    capacity: usize,
+    sender: broadcast::Sender<LogRecord>,
+}
+
+impl LogRingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.clamp(1, 1024));
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            sender,
+        }
+    }
+
+    pub fn push(&self, record: LogRecord) {
+        if let Ok(mut records) = self.records.lock() {
+            if records.len() >= self.capacity {
+                records.pop_front();
+            }
+            if self.capacity > 0 {
+                records.push_back(record.clone());
+            }
+        }
+        let _ = self.sender.send(record);
+    }
+
+    /// records matched `query`, oldest first
+    pub fn records(&self, query: &LogQuery) -> Vec<LogRecord> {
+        let Ok(records) = self.records.lock() else {
+            return vec![];
+        };
+        let mut matched = records
+            .iter()
+            .filter(|record| query.matches(record))
+            .cloned()
+            .collect::<Vec<LogRecord>>();
+        if let Some(limit) = query.limit {
+            matched.drain(..matched.len().saturating_sub(limit));
+        }
+        matched
+    }
+
+    /// receive events pushed after subscribe
+    pub fn subscribe(&self) -> broadcast::Receiver<LogRecord> {
+        self.sender.subscribe()
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name().eq("message") {
+            self.message = value.to_owned();
+        } else {
+            self.fields.push(format!("{}={value}", field.name()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name().eq("message") {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields.push(format!("{}={value:?}", field.name()));
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogRingBuffer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for field in visitor.fields {
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&field);
+        }
+        let metadata = event.metadata();
+        self.push(LogRecord {
+            time: default_formatted_now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_owned(),
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn ring_buffer_capacity_and_query() {
+        let buffer = LogRingBuffer::new(3);
+        let subscriber = tracing_subscriber::registry().with(buffer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("first");
+            tracing::info!(id = 1, "second");
+            tracing::warn!(target: "other", "third");
+            tracing::error!("fourth");
+        });
+        let all = buffer.records(&LogQuery::default());
+        assert_eq!(
+            all.iter()
+                .map(|r| r.message.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["second id=1", "third", "fourth"]
+        );
+        let query = LogQuery {
+            level: Some("warn".to_owned()),
+            target: Some("awesome_operates".to_owned()),
+            limit: None,
+        };
+        assert_eq!(buffer.records(&query)[0].message, "fourth");
+        let query = LogQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(buffer.records(&query)[0].message, "fourth");
+    }
+}
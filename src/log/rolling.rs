@@ -0,0 +1,375 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Timelike};
+use tracing_appender::rolling::Rotation;
+
+use crate::compress::{gzip_and_remove, sync_gzip_and_remove};
+
+/// retention policy used by [`RollingWriter`]
+/// ```rust
+/// use awesome_operates::log::LogRetention;
+/// use tracing_appender::rolling::Rotation;
+///
+/// let retention = LogRetention {
+///     rotation: Rotation::DAILY,
+///     max_file_size: Some(10 * 1024 * 1024),
+///     max_files: Some(7),
+///     max_total_size: Some(100 * 1024 * 1024),
+///     compress: true,
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogRetention {
+    /// time based rotation, `Rotation::NEVER` for size based rotation only
+    pub rotation: Rotation,
+    /// rotate when the active file will exceed this size in bytes
+    pub max_file_size: Option<u64>,
+    /// max count of rotated files kept, the active file is not included
+    pub max_files: Option<usize>,
+    /// max bytes of the active file and rotated files, checked at every rotation,
+    /// the oldest rotated files are removed first
+    pub max_total_size: Option<u64>,
+    /// gzip rotated files into `*.gz`
+    pub compress: bool,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            rotation: Rotation::DAILY,
+            max_file_size: None,
+            max_files: None,
+            max_total_size: None,
+            compress: false,
+        }
+    }
+}
+
+/// write into `{dir}/{filename}`, rotated files are renamed as `{filename}.{%Y%m%d%H%M%S}`
+/// rotated files are compressed and cleaned in the tokio runtime which create the writer,
+/// or right in `write` when it's created without a runtime
+pub struct RollingWriter {
+    dir: PathBuf,
+    filename: String,
+    retention: LogRetention,
+    file: File,
+    size: u64,
+    /// start of the next rotation period, `None` for `Rotation::NEVER`
+    next_rotation: Option<SystemTime>,
+    runtime: Option<tokio::runtime::Handle>,
+    /// compression and cleanup of rotated files in the runtime
+    pending: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl RollingWriter {
+    pub fn new(
+        dir: impl AsRef<Path>,
+        filename: impl Into<String>,
+        retention: LogRetention,
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let filename = filename.into();
+        std::fs::create_dir_all(&dir)?;
+        let file = open_append(&dir.join(&filename))?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+        Ok(Self {
+            next_rotation: next_rotation(&retention.rotation, modified.into()),
+            size: metadata.len(),
+            dir,
+            filename,
+            retention,
+            file,
+            runtime: tokio::runtime::Handle::try_current().ok(),
+            pending: vec![],
+        })
+    }
+
+    /// wait the compression and cleanup of rotated files spawned so far
+    pub async fn wait_rotated(&mut self) {
+        for task in std::mem::take(&mut self.pending) {
+            let _ = task.await;
+        }
+    }
+
+    pub fn active_filepath(&self) -> PathBuf {
+        self.dir.join(&self.filename)
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        let oversize = self.size > 0
+            && self
+                .retention
+                .max_file_size
+                .is_some_and(|max| self.size + incoming as u64 > max);
+        oversize
+            || self
+                .next_rotation
+                .is_some_and(|next| SystemTime::now() >= next)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let active = self.active_filepath();
+        if self.size > 0 {
+            let rotated = self.rotated_filepath();
+            std::fs::rename(&active, &rotated)?;
+            self.after_rotate(rotated);
+        }
+        self.file = open_append(&active)?;
+        self.size = 0;
+        self.next_rotation = next_rotation(&self.retention.rotation, Local::now());
+        Ok(())
+    }
+
+    fn rotated_filepath(&self) -> PathBuf {
+        let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+        let base = format!("{}.{timestamp}", self.filename);
+        let mut index = 0;
+        loop {
+            let name = match index {
+                0 => base.clone(),
+                _ => format!("{base}-{index}"),
+            };
+            let path = self.dir.join(&name);
+            if !path.exists() && !self.dir.join(format!("{name}.gz")).exists() {
+                return path;
+            }
+            index += 1;
+        }
+    }
+
+    fn after_rotate(&mut self, rotated: PathBuf) {
+        let dir = self.dir.clone();
+        let filename = self.filename.clone();
+        let retention = self.retention.clone();
+        match &self.runtime {
+            Some(runtime) => {
+                self.pending.retain(|task| !task.is_finished());
+                let task = runtime.spawn(async move {
+                    if retention.compress {
+                        gzip_and_remove(&rotated).await.unwrap_or_else(|e| {
+                            tracing::warn!("compress rotated log failed with `{e:?}`")
+                        });
+                    }
+                    cleanup_rotated_files(&dir, &filename, &retention)
+                        .unwrap_or_else(|e| tracing::warn!("clean rotated logs failed `{e:?}`"));
+                });
+                self.pending.push(task);
+            }
+            None => {
+                if retention.compress {
+                    sync_gzip_and_remove(&rotated).unwrap_or_else(|e| {
+                        tracing::warn!("compress rotated log failed with `{e:?}`")
+                    });
+                }
+                cleanup_rotated_files(&dir, &filename, &retention)
+                    .unwrap_or_else(|e| tracing::warn!("clean rotated logs failed `{e:?}`"))
+            }
+        }
+    }
+}
+
+impl Write for RollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// start of the period after the one `time` is in, `None` for `Rotation::NEVER`
+fn next_rotation(rotation: &Rotation, time: DateTime<Local>) -> Option<SystemTime> {
+    let date = time.date_naive();
+    let next = if rotation.eq(&Rotation::MINUTELY) {
+        date.and_hms_opt(time.hour(), time.minute(), 0)? + Duration::minutes(1)
+    } else if rotation.eq(&Rotation::HOURLY) {
+        date.and_hms_opt(time.hour(), 0, 0)? + Duration::hours(1)
+    } else if rotation.eq(&Rotation::DAILY) {
+        date.succ_opt()?.and_hms_opt(0, 0, 0)?
+    } else if rotation.eq(&Rotation::WEEKLY) {
+        let monday = date - Duration::days(time.weekday().num_days_from_monday() as i64);
+        (monday + Duration::days(7)).and_hms_opt(0, 0, 0)?
+    } else {
+        return None;
+    };
+    // a local time skipped by daylight saving is taken as utc, off by the shift at most
+    let next = Local
+        .from_local_datetime(&next)
+        .earliest()
+        .unwrap_or_else(|| Local.from_utc_datetime(&next));
+    Some(next.into())
+}
+
+/// remove the oldest rotated files of `filename` under `dir` by `max_files` and `max_total_size`
+pub fn cleanup_rotated_files(
+    dir: impl AsRef<Path>,
+    filename: &str,
+    retention: &LogRetention,
+) -> io::Result<()> {
+    let rotated_prefix = format!("{filename}.");
+    let mut rotated = vec![];
+    let mut total_size = 0;
+    for entry in std::fs::read_dir(&dir)?.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        if name.eq(filename) {
+            total_size += metadata.len();
+        } else if name.starts_with(&rotated_prefix) {
+            total_size += metadata.len();
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            rotated.push((modified, name, metadata.len()));
+        }
+    }
+    // newest first
+    rotated.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+    let max_files = retention.max_files.unwrap_or(usize::MAX);
+    let max_total_size = retention.max_total_size.unwrap_or(u64::MAX);
+    let mut kept = rotated.len();
+    for (_, name, size) in rotated.iter().rev() {
+        if kept <= max_files && total_size <= max_total_size {
+            break;
+        }
+        tracing::debug!("remove rotated log {name}");
+        match std::fs::remove_file(dir.as_ref().join(name)) {
+            Ok(_) => {}
+            Err(e) if e.kind().eq(&io::ErrorKind::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        kept -= 1;
+        total_size = total_size.saturating_sub(*size);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotated_names(dir: &Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("agent.log."))
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotate_by_size_and_keep_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = LogRetention {
+            rotation: Rotation::NEVER,
+            max_file_size: Some(10),
+            max_files: Some(2),
+            ..Default::default()
+        };
+        let mut writer = RollingWriter::new(dir.path(), "agent.log", retention).unwrap();
+        for _ in 0..5 {
+            writer.write_all(b"0123456789").unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(rotated_names(dir.path()).len(), 2);
+        assert_eq!(
+            std::fs::read(writer.active_filepath()).unwrap(),
+            b"0123456789"
+        );
+    }
+
+    #[test]
+    fn cleanup_by_total_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = LogRetention {
+            rotation: Rotation::NEVER,
+            max_file_size: Some(10),
+            max_total_size: Some(25),
+            ..Default::default()
+        };
+        let mut writer = RollingWriter::new(dir.path(), "agent.log", retention).unwrap();
+        for _ in 0..6 {
+            writer.write_all(b"0123456789").unwrap();
+        }
+        // checked at rotation, the new active file is empty at that time
+        assert_eq!(rotated_names(dir.path()).len(), 2);
+    }
+
+    #[test]
+    fn compress_without_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = LogRetention {
+            rotation: Rotation::NEVER,
+            max_file_size: Some(10),
+            compress: true,
+            ..Default::default()
+        };
+        let mut writer = RollingWriter::new(dir.path(), "agent.log", retention).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        let names = rotated_names(dir.path());
+        assert_eq!(names.len(), 1);
+        assert!(names[0].ends_with(".gz"));
+    }
+
+    #[test]
+    fn next_rotation_start() {
+        let time = Local.with_ymd_and_hms(2024, 5, 15, 13, 45, 30).unwrap();
+        let at =
+            |y, m, d, h, min| SystemTime::from(Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap());
+        assert_eq!(
+            next_rotation(&Rotation::MINUTELY, time),
+            Some(at(2024, 5, 15, 13, 46))
+        );
+        assert_eq!(
+            next_rotation(&Rotation::HOURLY, time),
+            Some(at(2024, 5, 15, 14, 0))
+        );
+        assert_eq!(
+            next_rotation(&Rotation::DAILY, time),
+            Some(at(2024, 5, 16, 0, 0))
+        );
+        // 2024-05-15 is a wednesday
+        assert_eq!(
+            next_rotation(&Rotation::WEEKLY, time),
+            Some(at(2024, 5, 20, 0, 0))
+        );
+        assert_eq!(next_rotation(&Rotation::NEVER, time), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn compress_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = LogRetention {
+            rotation: Rotation::NEVER,
+            max_file_size: Some(10),
+            compress: true,
+            ..Default::default()
+        };
+        let mut writer = RollingWriter::new(dir.path(), "agent.log", retention).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.wait_rotated().await;
+        let names = rotated_names(dir.path());
+        assert_eq!(names.len(), 1);
+        assert!(names[0].ends_with(".gz"));
+    }
+}
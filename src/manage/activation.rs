@@ -0,0 +1,287 @@
+use std::mem::ManuallyDrop;
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use once_cell::sync::OnceCell;
+use snafu::ResultExt;
+use tokio::net::{TcpListener, ToSocketAddrs, UnixListener};
+
+use crate::error::{CommonIoSnafu, NotSocketSnafu, Result, UnsupportedListenFdSnafu};
+
+/// the first fd passed by systemd, `SD_LISTEN_FDS_START`
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// fds passed by systemd, read once by `init_listen_fds` or on first use
+static LISTEN_FDS: OnceCell<Mutex<Vec<ListenFd>>> = OnceCell::new();
+
+/// read the socket activation env and mark the fds close-on-exec, so child processes don't inherit them
+/// call it at the start of `main` before the tokio runtime or any other thread starts, the env is unset then
+/// without it the env is read on first use and left as is, a child never matches the stale `LISTEN_PID`
+pub fn init_listen_fds() {
+    LISTEN_FDS.get_or_init(|| {
+        let fds = read_listen_fds();
+        for key in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            std::env::remove_var(key);
+        }
+        fds
+    });
+}
+
+fn listen_fds_cell() -> &'static Mutex<Vec<ListenFd>> {
+    LISTEN_FDS.get_or_init(read_listen_fds)
+}
+
+fn read_listen_fds() -> Mutex<Vec<ListenFd>> {
+    let fds = parse_listen_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_FDNAMES").ok().as_deref(),
+        std::process::id(),
+    );
+    for listen_fd in &fds {
+        set_cloexec(listen_fd.fd);
+    }
+    tracing::debug!("socket activation fds {fds:?}");
+    Mutex::new(fds)
+}
+
+fn set_cloexec(fd: RawFd) {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0 {
+        tracing::warn!(
+            "set close-on-exec on fd {fd} failed: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenFd {
+    pub fd: RawFd,
+    /// `FileDescriptorName=` of the socket unit, systemd sets the socket unit name by default
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ActivatedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListenerKind {
+    Tcp,
+    Unix,
+}
+
+/// parse the socket activation env, empty when `listen_pid` is not `pid`
+pub fn parse_listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+    pid: u32,
+) -> Vec<ListenFd> {
+    if listen_pid
+        .and_then(|v| v.parse::<u32>().ok())
+        .ne(&Some(pid))
+    {
+        return vec![];
+    }
+    let count = listen_fds
+        .and_then(|v| v.parse::<RawFd>().ok())
+        .unwrap_or_default();
+    let names = listen_fdnames
+        .map(|v| v.split(':').collect::<Vec<&str>>())
+        .unwrap_or_default();
+    (0..count.max(0))
+        .map(|index| ListenFd {
+            fd: LISTEN_FDS_START + index,
+            name: names
+                .get(index as usize)
+                .filter(|name| !name.is_empty())
+                .map(|name| name.to_string()),
+        })
+        .collect()
+}
+
+/// fds passed by systemd and not taken yet
+pub fn listen_fds() -> Vec<ListenFd> {
+    listen_fds_cell()
+        .lock()
+        .map(|fds| fds.clone())
+        .unwrap_or_default()
+}
+
+/// take every remaining fd passed by systemd as listeners
+pub fn activated_listeners() -> Result<Vec<(Option<String>, ActivatedListener)>> {
+    let fds = listen_fds_cell()
+        .lock()
+        .map(|mut fds| std::mem::take(&mut *fds))
+        .unwrap_or_default();
+    fds.into_iter()
+        .map(|listen_fd| Ok((listen_fd.name, listener_from_fd(listen_fd.fd)?)))
+        .collect()
+}
+
+/// the tcp listener passed by systemd, or bind `addr` when not socket activated
+/// `name` matches `FileDescriptorName=` when there are multiple sockets
+/// ```rust,no_run
+/// # async {
+/// let listener = awesome_operates::manage::tcp_listener_or_bind(None, "0.0.0.0:3000")
+///     .await
+///     .unwrap();
+/// axum::serve(listener, axum::Router::new()).await.unwrap();
+/// # };
+/// ```
+pub async fn tcp_listener_or_bind<A: ToSocketAddrs>(
+    name: Option<&str>,
+    addr: A,
+) -> Result<TcpListener> {
+    if let Some(fd) = take_listen_fd(name, ListenerKind::Tcp) {
+        tracing::info!("use socket activation fd {fd} as tcp listener");
+        if let ActivatedListener::Tcp(listener) = listener_from_fd(fd)? {
+            return Ok(listener);
+        }
+    }
+    TcpListener::bind(addr).await.context(CommonIoSnafu)
+}
+
+/// the unix listener passed by systemd, or bind `path` when not socket activated
+/// a stale socket file at `path` is removed before binding, any other file at `path` is an error
+pub async fn unix_listener_or_bind(
+    name: Option<&str>,
+    path: impl AsRef<Path>,
+) -> Result<UnixListener> {
+    if let Some(fd) = take_listen_fd(name, ListenerKind::Unix) {
+        tracing::info!("use socket activation fd {fd} as unix listener");
+        if let ActivatedListener::Unix(listener) = listener_from_fd(fd)? {
+            return Ok(listener);
+        }
+    }
+    let path = path.as_ref();
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            tokio::fs::remove_file(path).await.context(CommonIoSnafu)?
+        }
+        Ok(_) => {
+            return NotSocketSnafu {
+                path: path.display().to_string(),
+            }
+            .fail()
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).context(CommonIoSnafu),
+    }
+    UnixListener::bind(path).context(CommonIoSnafu)
+}
+
+fn take_listen_fd(name: Option<&str>, kind: ListenerKind) -> Option<RawFd> {
+    let mut fds = listen_fds_cell().lock().ok()?;
+    let index = fds.iter().position(|listen_fd| {
+        name.is_none_or(|name| listen_fd.name.as_deref().eq(&Some(name)))
+            && listener_kind(listen_fd.fd).eq(&Some(kind))
+    })?;
+    Some(fds.remove(index).fd)
+}
+
+/// check the socket type and family without taking the ownership of `fd`
+fn listener_kind(fd: RawFd) -> Option<ListenerKind> {
+    if socket_type(fd)? != libc::SOCK_STREAM {
+        return None;
+    }
+    let tcp = ManuallyDrop::new(unsafe { std::net::TcpListener::from_raw_fd(fd) });
+    if tcp.local_addr().is_ok() {
+        return Some(ListenerKind::Tcp);
+    }
+    let unix = ManuallyDrop::new(unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) });
+    unix.local_addr().is_ok().then_some(ListenerKind::Unix)
+}
+
+/// `SO_TYPE` of the socket, `None` when `fd` is not a socket
+fn socket_type(fd: RawFd) -> Option<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            (&mut value as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(value)
+}
+
+/// take the ownership of `fd`, which must be a listening socket passed by systemd
+fn listener_from_fd(fd: RawFd) -> Result<ActivatedListener> {
+    match listener_kind(fd) {
+        Some(ListenerKind::Tcp) => {
+            let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+            listener.set_nonblocking(true).context(CommonIoSnafu)?;
+            Ok(ActivatedListener::Tcp(
+                TcpListener::from_std(listener).context(CommonIoSnafu)?,
+            ))
+        }
+        Some(ListenerKind::Unix) => {
+            let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+            listener.set_nonblocking(true).context(CommonIoSnafu)?;
+            Ok(ActivatedListener::Unix(
+                UnixListener::from_std(listener).context(CommonIoSnafu)?,
+            ))
+        }
+        None => UnsupportedListenFdSnafu { fd }.fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::{AsRawFd, IntoRawFd};
+
+    use super::*;
+
+    #[test]
+    fn parse_env() {
+        assert!(parse_listen_fds(Some("1"), Some("2"), None, 2).is_empty());
+        assert_eq!(
+            parse_listen_fds(Some("2"), Some("2"), Some("http:"), 2),
+            vec![
+                ListenFd {
+                    fd: 3,
+                    name: Some("http".to_owned())
+                },
+                ListenFd { fd: 4, name: None }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn listener_from_raw_fd() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let ActivatedListener::Tcp(listener) = listener_from_fd(tcp.into_raw_fd()).unwrap() else {
+            panic!("not a tcp listener");
+        };
+        assert_eq!(listener.local_addr().unwrap(), addr);
+
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(listener_kind(udp.as_raw_fd()).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("activation.sock");
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        assert!(matches!(
+            listener_from_fd(unix.into_raw_fd()).unwrap(),
+            ActivatedListener::Unix(_)
+        ));
+        let listener = unix_listener_or_bind(None, &path).await.unwrap();
+        assert!(listener.local_addr().unwrap().as_pathname().is_some());
+
+        let regular = dir.path().join("data.txt");
+        std::fs::write(&regular, "keep").unwrap();
+        assert!(unix_listener_or_bind(None, &regular).await.is_err());
+        assert_eq!(std::fs::read_to_string(&regular).unwrap(), "keep");
+    }
+}
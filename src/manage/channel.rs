@@ -0,0 +1,370 @@
+use std::path::{Path, PathBuf};
+
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use snafu::ResultExt;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+
+use super::{CommandRunner, UpdateManifest, Updater};
+use crate::error::{
+    ChecksumMismatchSnafu, CommonIoSnafu, Result, UpdateRequestSnafu, UpdateResponseStatusSnafu,
+};
+use crate::helper::{get_pkg_version, sha256_file, Version};
+use crate::verify::{signature_path, SIGNATURE_SUFFIX};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateCheck {
+    UpToDate,
+    Available(UpdateManifest),
+    /// current version is older than `min_version` of the manifest
+    Incompatible(UpdateManifest),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadProgress {
+    /// `resumed` bytes are reused from a previous partial download
+    Started {
+        resumed: u64,
+        total: Option<u64>,
+    },
+    Downloading {
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    Finished {
+        path: PathBuf,
+    },
+}
+
+/// poll an update channel manifest and download new versions
+/// ```rust,no_run
+/// use awesome_operates::manage::{UpdateClient, Updater};
+///
+/// # async {
+/// let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+/// tokio::spawn(async move {
+///     while let Some(progress) = rx.recv().await {
+///         tracing::info!("{progress:?}");
+///     }
+/// });
+/// let client = UpdateClient::new("https://example.com/agent/stable.json")
+///     .current_version(env!("CARGO_PKG_VERSION"))
+///     .download_dir("/var/lib/agent/updates")
+///     .progress(tx);
+/// let updater = Updater::current_exe().unwrap();
+/// if let Some(manifest) = client.check_and_apply(&updater).await.unwrap() {
+///     tracing::info!("updated into {}, restart now", manifest.version);
+/// }
+/// # };
+/// ```
+#[derive(Debug, Clone)]
+pub struct UpdateClient {
+    manifest_url: String,
+    current_version: String,
+    download_dir: PathBuf,
+    client: reqwest::Client,
+    progress: Option<Sender<DownloadProgress>>,
+}
+
+impl UpdateClient {
+    pub fn new(manifest_url: impl Into<String>) -> Self {
+        Self {
+            manifest_url: manifest_url.into(),
+            current_version: get_pkg_version().to_owned(),
+            download_dir: std::env::temp_dir(),
+            client: reqwest::Client::new(),
+            progress: None,
+        }
+    }
+
+    /// defaults to `helper::get_pkg_version`
+    pub fn current_version(mut self, version: impl Into<String>) -> Self {
+        self.current_version = version.into();
+        self
+    }
+
+    pub fn download_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.download_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn progress(mut self, tx: Sender<DownloadProgress>) -> Self {
+        self.progress = Some(tx);
+        self
+    }
+
+    pub async fn fetch_manifest(&self) -> Result<UpdateManifest> {
+        let url = &self.manifest_url;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context(UpdateRequestSnafu { url })?;
+        if !response.status().is_success() {
+            return UpdateResponseStatusSnafu {
+                url,
+                status: response.status(),
+            }
+            .fail();
+        }
+        let mut manifest: UpdateManifest =
+            response.json().await.context(UpdateRequestSnafu { url })?;
+        // relative to the manifest url
+        if let Ok(download_url) = reqwest::Url::parse(url).and_then(|base| base.join(&manifest.url))
+        {
+            manifest.url = download_url.to_string();
+        }
+        Ok(manifest)
+    }
+
+    pub async fn check(&self) -> Result<UpdateCheck> {
+        let manifest = self.fetch_manifest().await?;
+        let current = Version::parse(&self.current_version)?;
+        if Version::parse(&manifest.version)? <= current {
+            return Ok(UpdateCheck::UpToDate);
+        }
+        if let Some(min_version) = &manifest.min_version {
+            if Version::parse(min_version)? > current {
+                tracing::warn!(
+                    "update {} requires at least {min_version}, current {current}",
+                    manifest.version
+                );
+                return Ok(UpdateCheck::Incompatible(manifest));
+            }
+        }
+        Ok(UpdateCheck::Available(manifest))
+    }
+
+    /// the file name of `manifest.url` in `download_dir`
+    pub fn download_path(&self, manifest: &UpdateManifest) -> PathBuf {
+        let filename = manifest
+            .url
+            .split(['?', '#'])
+            .next()
+            .and_then(|url| url.rsplit('/').next())
+            .filter(|filename| !filename.is_empty())
+            .map(|filename| filename.to_owned())
+            .unwrap_or_else(|| format!("update-{}", manifest.version));
+        self.download_dir.join(filename)
+    }
+
+    /// download into `download_path`, resume from the `.part` file left by a broken download
+    /// the detached signature is downloaded beside when the channel has one
+    pub async fn download(&self, manifest: &UpdateManifest) -> Result<PathBuf> {
+        let path = self.download_path(manifest);
+        let mut partial = path.as_os_str().to_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        tokio::fs::create_dir_all(&self.download_dir)
+            .await
+            .context(CommonIoSnafu)?;
+
+        let url = &manifest.url;
+        let mut resumed = tokio::fs::metadata(&partial)
+            .await
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        let mut request = self.client.get(url);
+        if resumed > 0 {
+            request = request.header(RANGE, format!("bytes={resumed}-"));
+        }
+        let mut response = request.send().await.context(UpdateRequestSnafu { url })?;
+        let status = response.status();
+        if status.eq(&StatusCode::RANGE_NOT_SATISFIABLE) {
+            // the partial file is complete already, or broken, the checksum decides
+            tracing::debug!("range not satisfiable, keep {} bytes", resumed);
+        } else if !status.is_success() {
+            return UpdateResponseStatusSnafu { url, status }.fail();
+        } else {
+            if status.ne(&StatusCode::PARTIAL_CONTENT) {
+                resumed = 0;
+            }
+            let total = response.content_length().map(|length| length + resumed);
+            self.send_progress(DownloadProgress::Started { resumed, total })
+                .await;
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed > 0)
+                .truncate(resumed == 0)
+                .open(&partial)
+                .await
+                .context(CommonIoSnafu)?;
+            let mut downloaded = resumed;
+            while let Some(chunk) = response.chunk().await.context(UpdateRequestSnafu { url })? {
+                file.write_all(&chunk).await.context(CommonIoSnafu)?;
+                downloaded += chunk.len() as u64;
+                self.send_progress(DownloadProgress::Downloading { downloaded, total })
+                    .await;
+            }
+            file.flush().await.context(CommonIoSnafu)?;
+        }
+
+        let actual = sha256_file(&partial).await?;
+        if !actual.eq_ignore_ascii_case(&manifest.sha256) {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return ChecksumMismatchSnafu {
+                filepath: partial.display().to_string(),
+                expected: &manifest.sha256,
+                actual,
+            }
+            .fail();
+        }
+        tokio::fs::rename(&partial, &path)
+            .await
+            .context(CommonIoSnafu)?;
+        self.download_signature(manifest, &path).await?;
+        self.send_progress(DownloadProgress::Finished { path: path.clone() })
+            .await;
+        Ok(path)
+    }
+
+    /// download and apply by `updater` when a compatible new version is available
+    pub async fn check_and_apply<R: CommandRunner>(
+        &self,
+        updater: &Updater<R>,
+    ) -> Result<Option<UpdateManifest>> {
+        let UpdateCheck::Available(manifest) = self.check().await? else {
+            return Ok(None);
+        };
+        let path = self.download(&manifest).await?;
+        updater.apply(&path, &manifest).await?;
+        Ok(Some(manifest))
+    }
+
+    /// a signature left by a previous download is removed first, so it never pairs with this file
+    /// only a 404 means the channel has no signature
+    async fn download_signature(&self, manifest: &UpdateManifest, path: &Path) -> Result<()> {
+        let signature_path = signature_path(path);
+        match tokio::fs::remove_file(&signature_path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).context(CommonIoSnafu)
+            }
+            _ => {}
+        }
+        let url = format!("{}{SIGNATURE_SUFFIX}", manifest.url);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context(UpdateRequestSnafu { url: &url })?;
+        let status = response.status();
+        if status.eq(&StatusCode::NOT_FOUND) {
+            tracing::debug!("no signature at {url}");
+            return Ok(());
+        }
+        if !status.is_success() {
+            return UpdateResponseStatusSnafu { url, status }.fail();
+        }
+        let content = response
+            .bytes()
+            .await
+            .context(UpdateRequestSnafu { url: &url })?;
+        tokio::fs::write(signature_path, content)
+            .await
+            .context(CommonIoSnafu)
+    }
+
+    /// `Downloading` is dropped when the receiver is behind, so a slow receiver never stalls the download
+    async fn send_progress(&self, progress: DownloadProgress) {
+        let Some(tx) = &self.progress else {
+            return;
+        };
+        match tx.try_send(progress) {
+            Err(TrySendError::Full(progress))
+                if !matches!(progress, DownloadProgress::Downloading { .. }) =>
+            {
+                let _ = tx.send(progress).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use tower_http::services::ServeDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn check_and_resume_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let serve_dir = dir.join("serve");
+        let download_dir = dir.join("download");
+        tokio::fs::create_dir_all(&serve_dir).await.unwrap();
+        tokio::fs::create_dir_all(&download_dir).await.unwrap();
+        let binary = "0123456789".repeat(1000);
+        tokio::fs::write(serve_dir.join("agent-0.2.0"), &binary)
+            .await
+            .unwrap();
+        let manifest = UpdateManifest {
+            version: "0.2.0".to_owned(),
+            sha256: sha256_file(serve_dir.join("agent-0.2.0")).await.unwrap(),
+            url: "agent-0.2.0".to_owned(),
+            min_version: Some("0.1.5".to_owned()),
+        };
+        tokio::fs::write(
+            serve_dir.join("stable.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().fallback_service(ServeDir::new(&serve_dir));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1024);
+        let client = UpdateClient::new(format!("http://{addr}/stable.json"))
+            .current_version("0.1.6")
+            .download_dir(&download_dir)
+            .progress(tx);
+        let UpdateCheck::Available(manifest) = client.check().await.unwrap() else {
+            panic!("update not available");
+        };
+        assert_eq!(manifest.url, format!("http://{addr}/agent-0.2.0"));
+        let client_old = client.clone().current_version("0.1.0");
+        assert!(matches!(
+            client_old.check().await.unwrap(),
+            UpdateCheck::Incompatible(_)
+        ));
+        let client_new = client.clone().current_version("0.2.0");
+        assert_eq!(client_new.check().await.unwrap(), UpdateCheck::UpToDate);
+
+        tokio::fs::write(download_dir.join("agent-0.2.0.part"), &binary[..4000])
+            .await
+            .unwrap();
+        tokio::fs::write(download_dir.join("agent-0.2.0.sig"), "stale")
+            .await
+            .unwrap();
+        let path = client.download(&manifest).await.unwrap();
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), binary);
+        // the channel has no signature, the stale one is removed
+        assert!(!signature_path(&path).exists());
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            DownloadProgress::Started {
+                resumed: 4000,
+                total: Some(10000)
+            }
+        );
+        let mut last = None;
+        while let Ok(progress) = rx.try_recv() {
+            last = Some(progress);
+        }
+        assert_eq!(last, Some(DownloadProgress::Finished { path }));
+    }
+}
@@ -0,0 +1,503 @@
+use snafu::ResultExt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+#[cfg(unix)]
+pub use activation::{
+    activated_listeners, init_listen_fds, listen_fds, parse_listen_fds, tcp_listener_or_bind,
+    unix_listener_or_bind, ActivatedListener, ListenFd, LISTEN_FDS_START,
+};
+pub use channel::{DownloadProgress, UpdateCheck, UpdateClient};
+#[cfg(unix)]
+pub use notify::{notify_until_shutdown_signal, Notifier, NotifyState, NOTIFY_SOCKET_ENV};
+pub use runner::{CommandRunner, RecordingRunner, ShellRunner};
+pub use scope::ServiceScope;
+pub use status::{JournalEntry, ServiceStatus, STATUS_PROPERTIES};
+pub use unit::{RestartPolicy, ServiceType, ServiceUnit, SocketUnit};
+pub use update::{PendingUpdate, StartupState, UpdateManifest, Updater, DEFAULT_MAX_ATTEMPTS};
+
+use crate::error::{CommandFailedSnafu, CommonIoSnafu, Result};
+use crate::{helper, verify};
+
+#[cfg(unix)]
+mod activation;
+mod channel;
+#[cfg(unix)]
+mod notify;
+mod runner;
+mod scope;
+mod status;
+mod unit;
+mod update;
+
+/// default directory of system unit files
+pub const DEFAULT_UNIT_DIR: &str = "/lib/systemd/system";
+
+/// manage systemd services by a `CommandRunner`
+/// ```rust
+/// use awesome_operates::manage::{RecordingRunner, ServiceManager, ServiceUnit};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let runner = RecordingRunner::default();
+/// let manager = ServiceManager::with_runner(runner.clone()).unit_dir("target/units");
+/// let unit = ServiceUnit::new("/usr/bin/agent");
+/// manager.register_unit("agent", &unit, false).await.unwrap();
+/// assert_eq!(
+///     runner.commands(),
+///     vec!["systemctl daemon-reload && systemctl enable agent"]
+/// );
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServiceManager<R: CommandRunner = ShellRunner> {
+    runner: R,
+    unit_dir: PathBuf,
+    /// `unit_dir` is set explicitly, `scope` keeps it
+    custom_unit_dir: bool,
+    scope: ServiceScope,
+    linger: bool,
+}
+
+impl Default for ServiceManager {
+    fn default() -> Self {
+        Self::with_runner(ShellRunner)
+    }
+}
+
+impl<R: CommandRunner> ServiceManager<R> {
+    pub fn with_runner(runner: R) -> Self {
+        Self {
+            runner,
+            unit_dir: PathBuf::from(DEFAULT_UNIT_DIR),
+            custom_unit_dir: false,
+            scope: ServiceScope::System,
+            linger: false,
+        }
+    }
+
+    /// switch to the unit directory and `systemctl` of `scope`, a `unit_dir` set before is kept
+    /// fails when the user config directory can't be found
+    pub fn scope(mut self, scope: ServiceScope) -> Result<Self> {
+        if !self.custom_unit_dir {
+            self.unit_dir = scope.unit_dir()?;
+        }
+        self.scope = scope;
+        Ok(self)
+    }
+
+    /// enable lingering before registering user services,
+    /// so they start at boot and keep running after logout
+    pub fn linger(mut self, linger: bool) -> Self {
+        self.linger = linger;
+        self
+    }
+
+    pub fn unit_dir(mut self, unit_dir: impl AsRef<Path>) -> Self {
+        self.unit_dir = unit_dir.as_ref().to_path_buf();
+        self.custom_unit_dir = true;
+        self
+    }
+
+    pub fn runner(&self) -> &R {
+        &self.runner
+    }
+
+    pub fn service_scope(&self) -> ServiceScope {
+        self.scope
+    }
+
+    pub fn service_config_path(&self, service_name: &str) -> PathBuf {
+        self.unit_dir.join(format!("{service_name}.service"))
+    }
+
+    pub fn socket_config_path(&self, service_name: &str) -> PathBuf {
+        self.unit_dir.join(format!("{service_name}.socket"))
+    }
+
+    /// register current program with command args as a service and enable it
+    pub async fn register_service(
+        &self,
+        service_name: &str,
+        exclude_args: &Vec<&str>,
+        restart: bool,
+    ) -> Result<Output> {
+        let unit = ServiceUnit::current_program(exclude_args)?;
+        self.register_unit(service_name, &unit, restart).await
+    }
+
+    /// write `unit` as `service_name` service and enable it
+    pub async fn register_unit(
+        &self,
+        service_name: &str,
+        unit: &ServiceUnit,
+        restart: bool,
+    ) -> Result<Output> {
+        let config_path = self.service_config_path(service_name);
+        helper::create_file_parent_dir(&config_path).await?;
+        tokio::fs::write(config_path, self.render_scoped(unit))
+            .await
+            .context(CommonIoSnafu)?;
+        let systemctl = self.scope.systemctl();
+        let mut command = format!(
+            "{}{systemctl} daemon-reload && {systemctl} enable {service_name}",
+            self.linger_prefix()
+        );
+        if restart {
+            command.push_str(&format!(" && {systemctl} restart {service_name}"));
+        }
+        self.runner.run(&command).await
+    }
+
+    /// write `unit` and the socket activating it, enable both
+    /// when `restart`, the service is stopped and started again by the first connection
+    pub async fn register_socket_unit(
+        &self,
+        service_name: &str,
+        unit: &ServiceUnit,
+        socket: &SocketUnit,
+        restart: bool,
+    ) -> Result<Output> {
+        for (config_path, content) in [
+            (
+                self.service_config_path(service_name),
+                self.render_scoped(unit),
+            ),
+            (self.socket_config_path(service_name), socket.render()),
+        ] {
+            helper::create_file_parent_dir(&config_path).await?;
+            tokio::fs::write(config_path, content)
+                .await
+                .context(CommonIoSnafu)?;
+        }
+        let systemctl = self.scope.systemctl();
+        let mut command = format!(
+            "{}{systemctl} daemon-reload && {systemctl} enable {service_name}.socket {service_name}",
+            self.linger_prefix()
+        );
+        if restart {
+            command.push_str(&format!(
+                " && {systemctl} stop {service_name} && {systemctl} restart {service_name}.socket"
+            ));
+        }
+        self.runner.run(&command).await
+    }
+
+    /// render `unit` with `multi-user.target` swapped for the target of the scope
+    fn render_scoped(&self, unit: &ServiceUnit) -> String {
+        let system_target = ServiceScope::System.default_target();
+        let mut unit = unit.clone();
+        for target in unit
+            .wanted_by
+            .iter_mut()
+            .filter(|t| t.as_str() == system_target)
+        {
+            *target = self.scope.default_target().to_owned();
+        }
+        unit.render()
+    }
+
+    /// stop, disable and remove the socket unit of `service_name`
+    pub async fn reset_socket_unit(&self, service_name: &str) -> Result<Output> {
+        let systemctl = self.scope.systemctl();
+        let command = format!(
+            "{systemctl} stop {service_name}.socket \
+            && {systemctl} disable {service_name}.socket \
+            && rm -f {} \
+            && {systemctl} daemon-reload",
+            self.socket_config_path(service_name).display()
+        );
+        self.runner.run(&command).await
+    }
+
+    ///reset service
+    /// stop the service
+    /// disable the service
+    pub async fn reset(&self, service_name: &str) -> Result<Output> {
+        let systemctl = self.scope.systemctl();
+        let command = format!(
+            "{systemctl} stop {service_name} \
+            && {systemctl} disable {service_name} \
+            && rm -f {} \
+            && {systemctl} daemon-reload",
+            self.service_config_path(service_name).display()
+        );
+        self.runner.run(&command).await
+    }
+
+    /// parsed from `systemctl show`, a missing unit is `not-found` rather than an error
+    pub async fn service_status(&self, service_name: &str) -> Result<ServiceStatus> {
+        let command = format!(
+            "{} show {service_name} --no-pager --property={}",
+            self.scope.systemctl(),
+            STATUS_PROPERTIES.join(",")
+        );
+        let output = self.run_success(&command).await?;
+        Ok(ServiceStatus::parse(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// the last `lines` journal entries of the service, oldest first
+    pub async fn recent_logs(&self, service_name: &str, lines: usize) -> Result<Vec<JournalEntry>> {
+        let command = format!(
+            "{} -u {service_name} -n {lines} -o json --no-pager",
+            self.scope.journalctl()
+        );
+        let output = self.run_success(&command).await?;
+        Ok(JournalEntry::parse_lines(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// keep user services of current user running without a login session
+    pub async fn enable_linger(&self) -> Result<Output> {
+        self.run_success("loginctl enable-linger \"$(id -un)\"")
+            .await
+    }
+
+    fn linger_prefix(&self) -> &'static str {
+        if self.linger && self.scope.eq(&ServiceScope::User) {
+            "loginctl enable-linger \"$(id -un)\" && "
+        } else {
+            ""
+        }
+    }
+
+    async fn run_success(&self, command: &str) -> Result<Output> {
+        let output = self.runner.run(command).await?;
+        if !output.status.success() {
+            return CommandFailedSnafu {
+                command,
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr),
+            }
+            .fail();
+        }
+        Ok(output)
+    }
+
+    /// check filepath binary can execute success
+    pub async fn binary_filepath_execute_success(&self, filepath: &str) -> Result<bool> {
+        tracing::debug!("check binary execute {filepath}");
+        if !Path::new(filepath).exists() {
+            return Ok(false);
+        }
+        #[cfg(unix)]
+        self.runner.run(&format!("chmod a+x {filepath}")).await?;
+        Ok(self
+            .runner
+            .run(&format!("{filepath} --version"))
+            .await?
+            .status
+            .success())
+    }
+}
+
+/// register current program with command args as a service and enable it
+pub async fn register_service(
+    service_name: &str,
+    exclude_args: &Vec<&str>,
+    restart: bool,
+) -> Result<Output> {
+    ServiceManager::default()
+        .register_service(service_name, exclude_args, restart)
+        .await
+}
+
+/// write `unit` as `service_name` service and enable it
+pub async fn register_unit(
+    service_name: &str,
+    unit: &ServiceUnit,
+    restart: bool,
+) -> Result<Output> {
+    ServiceManager::default()
+        .register_unit(service_name, unit, restart)
+        .await
+}
+
+///reset service
+/// stop the service
+/// disable the service
+pub async fn reset(service_name: &str) -> Result<Output> {
+    ServiceManager::default().reset(service_name).await
+}
+
+/// parsed from `systemctl show`, a missing unit is `not-found` rather than an error
+pub async fn service_status(service_name: &str) -> Result<ServiceStatus> {
+    ServiceManager::default().service_status(service_name).await
+}
+
+/// the last `lines` journal entries of the service by `journalctl -o json`
+pub async fn recent_logs(service_name: &str, lines: usize) -> Result<Vec<JournalEntry>> {
+    ServiceManager::default()
+        .recent_logs(service_name, lines)
+        .await
+}
+
+#[inline]
+pub fn service_config_path(service_name: &str) -> String {
+    format!("{DEFAULT_UNIT_DIR}/{service_name}.service")
+}
+
+/// rename without backup or checksum, use `Updater` for rollback support
+/// refuse the update when `update_filepath.sig` doesn't match, see `verify::allow_unsigned_updates`
+/// the verified bytes are staged, executed and installed, so a file swapped after the check is never run
+pub async fn check_update_binary(update_filepath: &str, original_filepath: &str) -> Result<()> {
+    tracing::debug!("check update binary");
+    if !Path::new(update_filepath).exists() {
+        return Ok(());
+    }
+    let update = verify::stage_update_file(update_filepath, original_filepath).await?;
+    if binary_filepath_execute_success(&update.path().to_string_lossy())
+        .await
+        .unwrap_or_default()
+    {
+        tracing::info!("install {update_filepath} into {original_filepath}");
+        update.install().await?;
+    } else {
+        update.discard().await?;
+    }
+    Ok(())
+}
+
+/// check filepath binary can execute success
+pub async fn binary_filepath_execute_success(filepath: &str) -> Result<bool> {
+    ServiceManager::default()
+        .binary_filepath_execute_success(filepath)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_manager() -> (ServiceManager<RecordingRunner>, tempfile::TempDir) {
+        let unit_dir = tempfile::tempdir().unwrap();
+        let runner = RecordingRunner::default();
+        (
+            ServiceManager::with_runner(runner).unit_dir(unit_dir.path()),
+            unit_dir,
+        )
+    }
+
+    #[tokio::test]
+    async fn register_and_reset_without_systemd() {
+        let (manager, unit_dir) = test_manager();
+        let unit = ServiceUnit::new("/usr/bin/agent");
+        manager.register_unit("agent", &unit, true).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(unit_dir.path().join("agent.service")).unwrap(),
+            unit.render()
+        );
+        manager.reset("agent").await.unwrap();
+        let commands = manager.runner().commands();
+        assert_eq!(
+            commands[0],
+            "systemctl daemon-reload && systemctl enable agent && systemctl restart agent"
+        );
+        assert!(commands[1].starts_with("systemctl stop agent"));
+        assert!(commands[1].contains(&format!(
+            "rm -f {}",
+            unit_dir.path().join("agent.service").display()
+        )));
+    }
+
+    #[tokio::test]
+    async fn register_socket_activated() {
+        let (manager, unit_dir) = test_manager();
+        let unit = ServiceUnit::new("/usr/bin/agent");
+        let socket = unit.socket_unit("3000");
+        manager
+            .register_socket_unit("agent", &unit, &socket, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(unit_dir.path().join("agent.socket")).unwrap(),
+            socket.render()
+        );
+        assert_eq!(
+            manager.runner().commands(),
+            vec!["systemctl daemon-reload && systemctl enable agent.socket agent"]
+        );
+    }
+
+    #[tokio::test]
+    async fn register_user_service() {
+        let (manager, unit_dir) = test_manager();
+        let manager = manager.scope(ServiceScope::User).unwrap().linger(true);
+        let unit = ServiceUnit::new("/usr/bin/agent").wanted_by("timers.target");
+        manager.register_unit("agent", &unit, true).await.unwrap();
+        let rendered = std::fs::read_to_string(unit_dir.path().join("agent.service")).unwrap();
+        assert!(rendered.contains("WantedBy=default.target timers.target"));
+        assert!(!rendered.contains("multi-user.target"));
+        manager.recent_logs("agent", 10).await.unwrap();
+        let commands = manager.runner().commands();
+        assert_eq!(
+            commands[0],
+            "loginctl enable-linger \"$(id -un)\" && systemctl --user daemon-reload \
+            && systemctl --user enable agent && systemctl --user restart agent"
+        );
+        assert!(commands[1].starts_with("journalctl --user -u agent"));
+    }
+
+    #[tokio::test]
+    async fn status_and_logs_by_runner() {
+        let show = std::fs::read_to_string("src/test_files/systemctl-show.txt").unwrap();
+        let journal = std::fs::read_to_string("src/test_files/journalctl.json").unwrap();
+        let runner = RecordingRunner::default()
+            .respond("systemctl show", 0, &show)
+            .respond("journalctl -u agent", 0, &journal)
+            .respond("journalctl -u missing", 1, "");
+        let manager = ServiceManager::with_runner(runner);
+        let status = manager.service_status("agent").await.unwrap();
+        assert_eq!(status.main_pid, Some(1234));
+        let logs = manager.recent_logs("agent", 3).await.unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(manager.recent_logs("missing", 3).await.is_err());
+        assert_eq!(
+            manager.runner().commands()[1],
+            "journalctl -u agent -n 3 -o json --no-pager"
+        );
+    }
+
+    #[tokio::test]
+    async fn binary_execute_by_runner() {
+        let (manager, _) = test_manager();
+        assert!(!manager
+            .binary_filepath_execute_success("/not-exists/agent")
+            .await
+            .unwrap());
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        assert!(manager.binary_filepath_execute_success(exe).await.unwrap());
+        assert_eq!(
+            manager.runner().commands().last().unwrap(),
+            &format!("{exe} --version")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn check_update_binary_verify_before_execute() {
+        use ed25519_dalek::Signer;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (update, original) = (dir.path().join("agent.update"), dir.path().join("agent"));
+        let (update, original) = (update.to_str().unwrap(), original.to_str().unwrap());
+        let script = b"#!/bin/sh\nexit 0\n";
+        tokio::fs::write(update, script).await.unwrap();
+        tokio::fs::write(original, "old").await.unwrap();
+        assert!(check_update_binary(update, original).await.is_err());
+        assert_eq!(tokio::fs::read(original).await.unwrap(), b"old");
+        assert!(!Path::new(&format!("{original}.new")).exists());
+
+        let signature = verify::test_signing_key().sign(script);
+        tokio::fs::write(verify::signature_path(update), signature.to_bytes())
+            .await
+            .unwrap();
+        check_update_binary(update, original).await.unwrap();
+        assert_eq!(tokio::fs::read(original).await.unwrap(), script);
+        assert!(!Path::new(update).exists());
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt::{Display, Formatter};
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+use snafu::ResultExt;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{CommonIoSnafu, Result};
+use crate::graceful::{shutdown_signal, shutdown_token};
+
+pub const NOTIFY_SOCKET_ENV: &str = "NOTIFY_SOCKET";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyState {
+    Ready,
+    Reloading,
+    Stopping,
+    Watchdog,
+    Status(String),
+    MainPid(u32),
+    Errno(i32),
+}
+
+impl Display for NotifyState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ready => write!(f, "READY=1"),
+            Self::Reloading => write!(f, "RELOADING=1"),
+            Self::Stopping => write!(f, "STOPPING=1"),
+            Self::Watchdog => write!(f, "WATCHDOG=1"),
+            Self::Status(status) => write!(f, "STATUS={}", status.replace('\n', " ")),
+            Self::MainPid(pid) => write!(f, "MAINPID={pid}"),
+            Self::Errno(errno) => write!(f, "ERRNO={errno}"),
+        }
+    }
+}
+
+/// sd_notify over the `NOTIFY_SOCKET` datagram socket, for `Type=notify` services
+/// every method does nothing and returns `Ok(false)` when not started by systemd
+/// ```rust,no_run
+/// use awesome_operates::manage::Notifier;
+///
+/// # async {
+/// let notifier = Notifier::from_env();
+/// notifier.status("loading config").unwrap();
+/// notifier.ready().unwrap();
+/// if let Some(interval) = Notifier::watchdog_interval() {
+///     notifier.spawn_watchdog(interval);
+/// }
+/// # };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    socket: Option<String>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Self {
+        Self {
+            socket: std::env::var(NOTIFY_SOCKET_ENV)
+                .ok()
+                .filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// a filesystem path, or an abstract socket name starts with `@`
+    pub fn new(socket: impl Into<String>) -> Self {
+        Self {
+            socket: Some(socket.into()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    pub fn notify(&self, states: &[NotifyState]) -> Result<bool> {
+        let Some(socket) = &self.socket else {
+            return Ok(false);
+        };
+        let message = states
+            .iter()
+            .map(|state| state.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        tracing::debug!("sd_notify `{message}` to {socket}");
+        let datagram = UnixDatagram::unbound().context(CommonIoSnafu)?;
+        send_to(&datagram, socket, message.as_bytes()).context(CommonIoSnafu)?;
+        Ok(true)
+    }
+
+    pub fn ready(&self) -> Result<bool> {
+        self.notify(&[NotifyState::Ready])
+    }
+
+    pub fn status(&self, status: impl Into<String>) -> Result<bool> {
+        self.notify(&[NotifyState::Status(status.into())])
+    }
+
+    pub fn stopping(&self) -> Result<bool> {
+        self.notify(&[NotifyState::Stopping])
+    }
+
+    pub fn watchdog(&self) -> Result<bool> {
+        self.notify(&[NotifyState::Watchdog])
+    }
+
+    /// half of `WATCHDOG_USEC` when the watchdog is enabled for current process
+    pub fn watchdog_interval() -> Option<Duration> {
+        if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+            if pid.ne(&std::process::id().to_string()) {
+                return None;
+            }
+        }
+        let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+        (usec > 0).then(|| Duration::from_micros(usec / 2))
+    }
+
+    /// send `WATCHDOG=1` every `interval` until `graceful::shutdown_token()` is cancelled
+    pub fn spawn_watchdog(&self, interval: Duration) -> JoinHandle<()> {
+        self.spawn_watchdog_with_shutdown(interval, shutdown_token())
+    }
+
+    pub fn spawn_watchdog_with_shutdown(
+        &self,
+        interval: Duration,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()> {
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        if let Err(e) = notifier.watchdog() {
+                            tracing::warn!("sd_notify watchdog failed `{e:?}`");
+                        }
+                    }
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+        })
+    }
+}
+
+/// notify `READY=1`, keep the watchdog alive, then notify `STOPPING=1` after `shutdown_signal`
+/// used for axum
+/// ```rust,no_run
+/// # async {
+/// let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+/// axum::serve(listener, axum::Router::new())
+///     .with_graceful_shutdown(awesome_operates::manage::notify_until_shutdown_signal())
+///     .await
+///     .unwrap();
+/// # };
+/// ```
+pub async fn notify_until_shutdown_signal() {
+    let notifier = Notifier::from_env();
+    if let Err(e) = notifier.ready() {
+        tracing::warn!("sd_notify ready failed `{e:?}`");
+    }
+    if let Some(interval) = Notifier::watchdog_interval() {
+        notifier.spawn_watchdog(interval);
+    }
+    shutdown_signal().await;
+    if let Err(e) = notifier.stopping() {
+        tracing::warn!("sd_notify stopping failed `{e:?}`");
+    }
+}
+
+fn send_to(datagram: &UnixDatagram, socket: &str, message: &[u8]) -> std::io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        return datagram.send_to_addr(message, &addr);
+    }
+    datagram.send_to(message, socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_local_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        let notifier = Notifier::new(path.to_str().unwrap());
+        assert!(notifier
+            .notify(&[
+                NotifyState::Ready,
+                NotifyState::Status("serving\nnow".to_owned())
+            ])
+            .unwrap());
+        let mut buf = [0; 128];
+        let size = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..size], b"READY=1\nSTATUS=serving now");
+
+        notifier.stopping().unwrap();
+        let size = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..size], b"STOPPING=1");
+
+        assert!(!Notifier::default().ready().unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+use std::process::{ExitStatus, Output};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use cfg_if::cfg_if;
+
+use crate::error::Result;
+use crate::helper;
+
+/// run a shell command, so service management can be tested without root and systemd
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    async fn run(&self, cmd: &str) -> Result<Output>;
+}
+
+/// run with `sh -c` by `helper::execute_command`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShellRunner;
+
+#[async_trait]
+impl CommandRunner for ShellRunner {
+    async fn run(&self, cmd: &str) -> Result<Output> {
+        helper::execute_command(cmd).await
+    }
+}
+
+/// record every command without running it
+/// the output is the first response whose pattern is contained in the command,
+/// or success with empty stdout
+/// ```rust
+/// use awesome_operates::manage::{CommandRunner, RecordingRunner};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let runner = RecordingRunner::default().respond("systemctl is-active", 3, "inactive\n");
+/// let output = runner.run("systemctl is-active agent").await.unwrap();
+/// assert!(!output.status.success());
+/// assert_eq!(runner.commands(), vec!["systemctl is-active agent"]);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordingRunner {
+    commands: Arc<Mutex<Vec<String>>>,
+    responses: Vec<(String, i32, String)>,
+}
+
+impl RecordingRunner {
+    pub fn respond(mut self, pattern: &str, code: i32, stdout: &str) -> Self {
+        self.responses
+            .push((pattern.to_owned(), code, stdout.to_owned()));
+        self
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        self.commands
+            .lock()
+            .map(|commands| commands.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl CommandRunner for RecordingRunner {
+    async fn run(&self, cmd: &str) -> Result<Output> {
+        tracing::debug!("record command `{cmd}`");
+        if let Ok(mut commands) = self.commands.lock() {
+            commands.push(cmd.to_owned());
+        }
+        let (code, stdout) = self
+            .responses
+            .iter()
+            .find(|(pattern, _, _)| cmd.contains(pattern))
+            .map(|(_, code, stdout)| (*code, stdout.clone()))
+            .unwrap_or_default();
+        Ok(Output {
+            status: exit_status(code),
+            stdout: stdout.into_bytes(),
+            stderr: vec![],
+        })
+    }
+}
+
+fn exit_status(code: i32) -> ExitStatus {
+    cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::unix::process::ExitStatusExt;
+
+            ExitStatus::from_raw(code << 8)
+        } else {
+            use std::os::windows::process::ExitStatusExt;
+
+            ExitStatus::from_raw(code as u32)
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use snafu::OptionExt;
+
+use super::DEFAULT_UNIT_DIR;
+use crate::error::{OptionNoneSnafu, Result};
+
+/// system manager run as root, or the per-user manager by `systemctl --user`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServiceScope {
+    #[default]
+    System,
+    User,
+}
+
+impl ServiceScope {
+    pub fn systemctl(&self) -> &'static str {
+        match self {
+            Self::System => "systemctl",
+            Self::User => "systemctl --user",
+        }
+    }
+
+    pub fn journalctl(&self) -> &'static str {
+        match self {
+            Self::System => "journalctl",
+            Self::User => "journalctl --user",
+        }
+    }
+
+    /// `WantedBy=` target, the user manager has no `multi-user.target`
+    pub fn default_target(&self) -> &'static str {
+        match self {
+            Self::System => "multi-user.target",
+            Self::User => "default.target",
+        }
+    }
+
+    /// `/lib/systemd/system`, or `$XDG_CONFIG_HOME/systemd/user` falls back to `~/.config/systemd/user`
+    pub fn unit_dir(&self) -> Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from(DEFAULT_UNIT_DIR)),
+            Self::User => {
+                let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+                    .filter(|v| !v.is_empty())
+                    .map(PathBuf::from)
+                    .or_else(|| {
+                        std::env::var_os("HOME")
+                            .filter(|v| !v.is_empty())
+                            .map(|home| PathBuf::from(home).join(".config"))
+                    })
+                    .context(OptionNoneSnafu)?;
+                Ok(config_dir.join("systemd").join("user"))
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::consts;
+
+/// properties queried by `systemctl show --property=`
+pub const STATUS_PROPERTIES: [&str; 11] = [
+    "Id",
+    "Description",
+    "LoadState",
+    "ActiveState",
+    "SubState",
+    "UnitFileState",
+    "MainPID",
+    "NRestarts",
+    "ExecMainStatus",
+    "ActiveEnterTimestamp",
+    "MemoryCurrent",
+];
+
+/// parsed from `systemctl show` key=value output
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct ServiceStatus {
+    pub id: String,
+    pub description: String,
+    /// `loaded`, `not-found`, `masked`...
+    pub load_state: String,
+    /// `active`, `inactive`, `failed`, `activating`...
+    pub active_state: String,
+    /// `running`, `dead`, `exited`...
+    pub sub_state: String,
+    /// `enabled`, `disabled`, empty for units without unit file
+    pub unit_file_state: String,
+    pub main_pid: Option<u32>,
+    pub restarts: u32,
+    pub exec_main_status: Option<i32>,
+    /// like `Mon 2024-01-15 10:00:00 CST`
+    pub active_enter_timestamp: Option<String>,
+    pub memory_current: Option<u64>,
+}
+
+impl ServiceStatus {
+    pub fn parse(output: &str) -> Self {
+        let properties = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect::<HashMap<&str, &str>>();
+        let text = |key: &str| properties.get(key).copied().unwrap_or_default().to_owned();
+        let non_empty = |key: &str| {
+            properties
+                .get(key)
+                .filter(|value| !value.is_empty() && value.ne(&&"n/a"))
+                .map(|value| value.to_string())
+        };
+        Self {
+            id: text("Id"),
+            description: text("Description"),
+            load_state: text("LoadState"),
+            active_state: text("ActiveState"),
+            sub_state: text("SubState"),
+            unit_file_state: text("UnitFileState"),
+            main_pid: non_empty("MainPID")
+                .and_then(|v| v.parse().ok())
+                .filter(|pid| *pid > 0),
+            restarts: non_empty("NRestarts")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            exec_main_status: non_empty("ExecMainStatus").and_then(|v| v.parse().ok()),
+            active_enter_timestamp: non_empty("ActiveEnterTimestamp"),
+            // `[not set]` or `infinity` when memory accounting is off
+            memory_current: non_empty("MemoryCurrent").and_then(|v| v.parse().ok()),
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.load_state.eq("loaded")
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_state.eq("active")
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.active_state.eq("failed")
+    }
+}
+
+/// one line of `journalctl -o json`
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// microseconds since epoch, `__REALTIME_TIMESTAMP`
+    pub timestamp: u64,
+    /// local time formatted by `consts::DEFAULT_TIME_FORMAT`
+    pub time: String,
+    /// syslog priority, 0 emerg to 7 debug
+    pub priority: Option<u8>,
+    pub pid: Option<u32>,
+    pub identifier: Option<String>,
+    pub message: String,
+}
+
+impl JournalEntry {
+    /// `None` when the line is not a journal json object
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = serde_json::from_str::<HashMap<String, Value>>(line).ok()?;
+        let text = |key: &str| fields.get(key).and_then(journal_field);
+        let timestamp = text("__REALTIME_TIMESTAMP")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or_default();
+        Some(Self {
+            timestamp,
+            time: chrono::Local
+                .timestamp_micros(timestamp as i64)
+                .single()
+                .map(|time| time.format(consts::DEFAULT_TIME_FORMAT).to_string())
+                .unwrap_or_default(),
+            priority: text("PRIORITY").and_then(|v| v.parse().ok()),
+            pid: text("_PID").and_then(|v| v.parse().ok()),
+            identifier: text("SYSLOG_IDENTIFIER"),
+            message: text("MESSAGE").unwrap_or_default(),
+        })
+    }
+
+    /// skip lines can't be parsed
+    pub fn parse_lines(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(Self::parse)
+            .collect()
+    }
+}
+
+/// journal fields are strings, or byte arrays for non-UTF-8 values
+fn journal_field(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Array(bytes) => {
+            let bytes = bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                .collect::<Vec<u8>>();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_systemctl_show() {
+        let status = ServiceStatus::parse(
+            &std::fs::read_to_string("src/test_files/systemctl-show.txt").unwrap(),
+        );
+        assert_eq!(status.id, "agent.service");
+        assert!(status.is_loaded() && status.is_active());
+        assert_eq!(status.sub_state, "running");
+        assert_eq!(status.main_pid, Some(1234));
+        assert_eq!(status.restarts, 2);
+        assert_eq!(status.exec_main_status, Some(0));
+        assert_eq!(
+            status.active_enter_timestamp.as_deref(),
+            Some("Mon 2024-01-15 10:00:00 UTC")
+        );
+        assert_eq!(status.memory_current, Some(10485760));
+
+        let status = ServiceStatus::parse(
+            "Id=missing.service\nLoadState=not-found\nActiveState=inactive\nMainPID=0\n\
+            NRestarts=0\nActiveEnterTimestamp=\nMemoryCurrent=[not set]\n",
+        );
+        assert!(!status.is_loaded());
+        assert_eq!(status.main_pid, None);
+        assert_eq!(status.active_enter_timestamp, None);
+        assert_eq!(status.memory_current, None);
+    }
+
+    #[test]
+    fn parse_journalctl_json() {
+        let entries = JournalEntry::parse_lines(
+            &std::fs::read_to_string("src/test_files/journalctl.json").unwrap(),
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].timestamp, 1705312800000000);
+        assert_eq!(entries[0].priority, Some(6));
+        assert_eq!(entries[0].pid, Some(1234));
+        assert_eq!(entries[0].identifier.as_deref(), Some("agent"));
+        assert_eq!(entries[0].message, "server listen on 0.0.0.0:3000");
+        assert_eq!(entries[2].message, "hello");
+    }
+}
@@ -0,0 +1,577 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use snafu::ResultExt;
+
+use crate::error::{CommonIoSnafu, Result};
+use crate::helper::get_program_args;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServiceType {
+    #[default]
+    Simple,
+    Exec,
+    Forking,
+    Oneshot,
+    Notify,
+    Idle,
+}
+
+impl Display for ServiceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Self::Simple => "simple",
+            Self::Exec => "exec",
+            Self::Forking => "forking",
+            Self::Oneshot => "oneshot",
+            Self::Notify => "notify",
+            Self::Idle => "idle",
+        };
+        write!(f, "{value}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    No,
+    #[default]
+    Always,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+    OnAbort,
+    OnWatchdog,
+}
+
+impl Display for RestartPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Self::No => "no",
+            Self::Always => "always",
+            Self::OnSuccess => "on-success",
+            Self::OnFailure => "on-failure",
+            Self::OnAbnormal => "on-abnormal",
+            Self::OnAbort => "on-abort",
+            Self::OnWatchdog => "on-watchdog",
+        };
+        write!(f, "{value}")
+    }
+}
+
+/// typed systemd `.service` unit
+/// ```rust
+/// use std::time::Duration;
+///
+/// use awesome_operates::manage::{RestartPolicy, ServiceType, ServiceUnit};
+///
+/// let unit = ServiceUnit::new("/usr/local/bin/agent --port 3000")
+///     .description("agent service")
+///     .service_type(ServiceType::Notify)
+///     .user("agent")
+///     .environment("RUST_LOG", "info")
+///     .restart(RestartPolicy::OnFailure)
+///     .restart_sec(Duration::from_secs(5))
+///     .limit_nofile(65535)
+///     .hardened();
+/// println!("{}", unit.render());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceUnit {
+    pub description: String,
+    pub documentation: Vec<String>,
+    pub after: Vec<String>,
+    pub before: Vec<String>,
+    pub wants: Vec<String>,
+    pub requires: Vec<String>,
+    pub service_type: ServiceType,
+    pub exec_start: String,
+    pub working_directory: Option<PathBuf>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub environment: Vec<(String, String)>,
+    pub environment_files: Vec<String>,
+    pub restart: RestartPolicy,
+    pub restart_sec: Option<Duration>,
+    pub watchdog_sec: Option<Duration>,
+    pub limit_nofile: Option<u64>,
+    /// like `512M`, `2G`, `80%`
+    pub memory_max: Option<String>,
+    /// other `[Service]` options, like hardening options `NoNewPrivileges=yes`
+    pub service_options: Vec<(String, String)>,
+    pub wanted_by: Vec<String>,
+}
+
+impl ServiceUnit {
+    pub fn new(exec_start: impl Into<String>) -> Self {
+        Self {
+            description: "agent service".to_owned(),
+            documentation: vec![],
+            after: vec!["network.target".to_owned()],
+            before: vec![],
+            wants: vec![],
+            requires: vec![],
+            service_type: ServiceType::default(),
+            exec_start: exec_start.into(),
+            working_directory: None,
+            user: None,
+            group: None,
+            environment: vec![],
+            environment_files: vec![],
+            restart: RestartPolicy::default(),
+            restart_sec: None,
+            watchdog_sec: None,
+            limit_nofile: None,
+            memory_max: None,
+            service_options: vec![],
+            wanted_by: vec!["multi-user.target".to_owned()],
+        }
+    }
+
+    /// current program with command args and current directory as working directory
+    pub fn current_program(exclude_args: &Vec<&str>) -> Result<Self> {
+        let exe_filepath = std::env::current_exe().context(CommonIoSnafu)?;
+        let work_directory = std::env::current_dir().context(CommonIoSnafu)?;
+        let exec_start = format!(
+            "{} {}",
+            exe_filepath.display(),
+            get_program_args(exclude_args).join(" ")
+        );
+        Ok(Self::new(exec_start.trim_end()).working_directory(work_directory))
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn documentation(mut self, url: impl Into<String>) -> Self {
+        self.documentation.push(url.into());
+        self
+    }
+
+    pub fn after(mut self, unit: impl Into<String>) -> Self {
+        self.after.push(unit.into());
+        self
+    }
+
+    pub fn before(mut self, unit: impl Into<String>) -> Self {
+        self.before.push(unit.into());
+        self
+    }
+
+    pub fn wants(mut self, unit: impl Into<String>) -> Self {
+        self.wants.push(unit.into());
+        self
+    }
+
+    pub fn requires(mut self, unit: impl Into<String>) -> Self {
+        self.requires.push(unit.into());
+        self
+    }
+
+    pub fn service_type(mut self, service_type: ServiceType) -> Self {
+        self.service_type = service_type;
+        self
+    }
+
+    pub fn working_directory(mut self, path: impl AsRef<Path>) -> Self {
+        self.working_directory = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    pub fn environment(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.environment.push((key.into(), value.into()));
+        self
+    }
+
+    /// prefix with `-` to ignore a missing file, like `-/etc/default/agent`
+    pub fn environment_file(mut self, path: impl Into<String>) -> Self {
+        self.environment_files.push(path.into());
+        self
+    }
+
+    pub fn restart(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn restart_sec(mut self, restart_sec: Duration) -> Self {
+        self.restart_sec = Some(restart_sec);
+        self
+    }
+
+    pub fn watchdog_sec(mut self, watchdog_sec: Duration) -> Self {
+        self.watchdog_sec = Some(watchdog_sec);
+        self
+    }
+
+    pub fn limit_nofile(mut self, limit: u64) -> Self {
+        self.limit_nofile = Some(limit);
+        self
+    }
+
+    pub fn memory_max(mut self, memory_max: impl Into<String>) -> Self {
+        self.memory_max = Some(memory_max.into());
+        self
+    }
+
+    pub fn service_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.service_options.push((key.into(), value.into()));
+        self
+    }
+
+    /// common hardening options that don't break a normal daemon
+    pub fn hardened(self) -> Self {
+        self.service_option("NoNewPrivileges", "yes")
+            .service_option("PrivateTmp", "yes")
+            .service_option("ProtectSystem", "full")
+            .service_option("ProtectKernelTunables", "yes")
+            .service_option("ProtectControlGroups", "yes")
+            .service_option("RestrictSUIDSGID", "yes")
+    }
+
+    pub fn wanted_by(mut self, target: impl Into<String>) -> Self {
+        self.wanted_by.push(target.into());
+        self
+    }
+
+    /// the matching `.socket` unit, register both by `ServiceManager::register_socket_unit`
+    pub fn socket_unit(&self, listen_stream: impl Into<String>) -> SocketUnit {
+        SocketUnit::new(listen_stream).description(format!("{} socket", self.description))
+    }
+
+    /// render into unit file content
+    pub fn render(&self) -> String {
+        let mut unit = UnitFile::default();
+        unit.section("Unit");
+        unit.entry("Description", &self.description);
+        unit.joined("Documentation", &self.documentation);
+        unit.joined("Wants", &self.wants);
+        unit.joined("Requires", &self.requires);
+        unit.joined("After", &self.after);
+        unit.joined("Before", &self.before);
+
+        unit.section("Service");
+        if self.service_type.ne(&ServiceType::Simple) {
+            unit.entry("Type", self.service_type);
+        }
+        unit.optional("User", self.user.as_ref());
+        unit.optional("Group", self.group.as_ref());
+        unit.optional(
+            "WorkingDirectory",
+            self.working_directory.as_ref().map(|v| v.display()),
+        );
+        for (key, value) in &self.environment {
+            unit.entry("Environment", quote_environment(key, value));
+        }
+        for path in &self.environment_files {
+            unit.entry("EnvironmentFile", path);
+        }
+        unit.entry("ExecStart", &self.exec_start);
+        unit.entry("Restart", self.restart);
+        unit.optional("RestartSec", self.restart_sec.map(timespan));
+        unit.optional("WatchdogSec", self.watchdog_sec.map(timespan));
+        unit.optional("LimitNOFILE", self.limit_nofile);
+        unit.optional("MemoryMax", self.memory_max.as_ref());
+        for (key, value) in &self.service_options {
+            unit.entry(key, value);
+        }
+
+        unit.section("Install");
+        unit.joined("WantedBy", &self.wanted_by);
+        unit.content
+    }
+}
+
+/// typed systemd `.socket` unit, activates the service with the same name by default
+/// ```rust
+/// use awesome_operates::manage::ServiceUnit;
+///
+/// let service = ServiceUnit::new("/usr/local/bin/agent").description("agent service");
+/// let socket = service
+///     .socket_unit("0.0.0.0:3000")
+///     .file_descriptor_name("http");
+/// assert!(socket.render().contains("ListenStream=0.0.0.0:3000"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketUnit {
+    pub description: String,
+    /// like `3000`, `0.0.0.0:3000`, `/run/agent.sock`
+    pub listen_streams: Vec<String>,
+    /// `FileDescriptorName=`, matched by `tcp_listener_or_bind` and `unix_listener_or_bind`
+    pub file_descriptor_name: Option<String>,
+    /// activated service like `agent.service`, the service with the same name when `None`
+    pub service: Option<String>,
+    pub socket_user: Option<String>,
+    pub socket_group: Option<String>,
+    /// like `0660`
+    pub socket_mode: Option<String>,
+    pub backlog: Option<u32>,
+    pub reuse_port: bool,
+    /// other `[Socket]` options
+    pub socket_options: Vec<(String, String)>,
+    pub wanted_by: Vec<String>,
+}
+
+impl SocketUnit {
+    pub fn new(listen_stream: impl Into<String>) -> Self {
+        Self {
+            description: "agent socket".to_owned(),
+            listen_streams: vec![listen_stream.into()],
+            file_descriptor_name: None,
+            service: None,
+            socket_user: None,
+            socket_group: None,
+            socket_mode: None,
+            backlog: None,
+            reuse_port: false,
+            socket_options: vec![],
+            wanted_by: vec!["sockets.target".to_owned()],
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn listen_stream(mut self, listen_stream: impl Into<String>) -> Self {
+        self.listen_streams.push(listen_stream.into());
+        self
+    }
+
+    pub fn file_descriptor_name(mut self, name: impl Into<String>) -> Self {
+        self.file_descriptor_name = Some(name.into());
+        self
+    }
+
+    pub fn service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    pub fn socket_user(mut self, user: impl Into<String>) -> Self {
+        self.socket_user = Some(user.into());
+        self
+    }
+
+    pub fn socket_group(mut self, group: impl Into<String>) -> Self {
+        self.socket_group = Some(group.into());
+        self
+    }
+
+    pub fn socket_mode(mut self, mode: impl Into<String>) -> Self {
+        self.socket_mode = Some(mode.into());
+        self
+    }
+
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = Some(backlog);
+        self
+    }
+
+    pub fn reuse_port(mut self) -> Self {
+        self.reuse_port = true;
+        self
+    }
+
+    pub fn socket_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.socket_options.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn wanted_by(mut self, target: impl Into<String>) -> Self {
+        self.wanted_by.push(target.into());
+        self
+    }
+
+    /// render into unit file content
+    pub fn render(&self) -> String {
+        let mut unit = UnitFile::default();
+        unit.section("Unit");
+        unit.entry("Description", &self.description);
+
+        unit.section("Socket");
+        for listen_stream in &self.listen_streams {
+            unit.entry("ListenStream", listen_stream);
+        }
+        unit.optional("FileDescriptorName", self.file_descriptor_name.as_ref());
+        unit.optional("Service", self.service.as_ref());
+        unit.optional("SocketUser", self.socket_user.as_ref());
+        unit.optional("SocketGroup", self.socket_group.as_ref());
+        unit.optional("SocketMode", self.socket_mode.as_ref());
+        unit.optional("Backlog", self.backlog);
+        if self.reuse_port {
+            unit.entry("ReusePort", "yes");
+        }
+        for (key, value) in &self.socket_options {
+            unit.entry(key, value);
+        }
+
+        unit.section("Install");
+        unit.joined("WantedBy", &self.wanted_by);
+        unit.content
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct UnitFile {
+    pub(crate) content: String,
+}
+
+impl UnitFile {
+    pub(crate) fn section(&mut self, name: &str) {
+        if !self.content.is_empty() {
+            self.content.push('\n');
+        }
+        self.content.push_str(&format!("[{name}]\n"));
+    }
+
+    pub(crate) fn entry(&mut self, key: &str, value: impl Display) {
+        self.content.push_str(&format!("{key}={value}\n"));
+    }
+
+    pub(crate) fn optional(&mut self, key: &str, value: Option<impl Display>) {
+        if let Some(value) = value {
+            self.entry(key, value);
+        }
+    }
+
+    pub(crate) fn joined(&mut self, key: &str, values: &[String]) {
+        if !values.is_empty() {
+            self.entry(key, values.join(" "));
+        }
+    }
+}
+
+/// systemd time span, like `5s`, `500ms`
+pub(crate) fn timespan(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+fn quote_environment(key: &str, value: &str) -> String {
+    let escaped = format!("{key}={value}")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_default_unit() {
+        let unit = ServiceUnit::new("/usr/bin/agent --port 3000").working_directory("/opt/agent");
+        assert_eq!(
+            unit.render(),
+            r#"[Unit]
+Description=agent service
+After=network.target
+
+[Service]
+WorkingDirectory=/opt/agent
+ExecStart=/usr/bin/agent --port 3000
+Restart=always
+
+[Install]
+WantedBy=multi-user.target
+"#
+        );
+    }
+
+    #[test]
+    fn render_full_unit() {
+        let unit = ServiceUnit::new("/usr/bin/agent")
+            .description("collect agent")
+            .documentation("https://example.com/agent")
+            .wants("network-online.target")
+            .after("network-online.target")
+            .service_type(ServiceType::Notify)
+            .user("agent")
+            .group("agent")
+            .environment("RUST_LOG", "info")
+            .environment("GREETING", r#"say "hi" 100%"#)
+            .environment_file("-/etc/default/agent")
+            .restart(RestartPolicy::OnFailure)
+            .restart_sec(Duration::from_millis(1500))
+            .watchdog_sec(Duration::from_secs(30))
+            .limit_nofile(65535)
+            .memory_max("512M")
+            .hardened();
+        assert_eq!(
+            unit.render(),
+            r#"[Unit]
+Description=collect agent
+Documentation=https://example.com/agent
+Wants=network-online.target
+After=network.target network-online.target
+
+[Service]
+Type=notify
+User=agent
+Group=agent
+Environment="RUST_LOG=info"
+Environment="GREETING=say \"hi\" 100%%"
+EnvironmentFile=-/etc/default/agent
+ExecStart=/usr/bin/agent
+Restart=on-failure
+RestartSec=1500ms
+WatchdogSec=30s
+LimitNOFILE=65535
+MemoryMax=512M
+NoNewPrivileges=yes
+PrivateTmp=yes
+ProtectSystem=full
+ProtectKernelTunables=yes
+ProtectControlGroups=yes
+RestrictSUIDSGID=yes
+
+[Install]
+WantedBy=multi-user.target
+"#
+        );
+    }
+
+    #[test]
+    fn render_socket_unit() {
+        let unit = ServiceUnit::new("/usr/bin/agent")
+            .socket_unit("0.0.0.0:3000")
+            .listen_stream("/run/agent.sock")
+            .file_descriptor_name("http")
+            .socket_mode("0660")
+            .backlog(1024);
+        assert_eq!(
+            unit.render(),
+            r#"[Unit]
+Description=agent service socket
+
+[Socket]
+ListenStream=0.0.0.0:3000
+ListenStream=/run/agent.sock
+FileDescriptorName=http
+SocketMode=0660
+Backlog=1024
+
+[Install]
+WantedBy=sockets.target
+"#
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::{OptionExt, ResultExt};
+
+use super::{CommandRunner, ServiceManager, ShellRunner};
+use crate::error::{
+    BackupMissingSnafu, BinaryCannotBeExecuteSnafu, ChecksumMismatchSnafu, CommonIoSnafu,
+    OptionNoneSnafu, Result, SerdeJsonSnafu,
+};
+use crate::helper::default_formatted_now;
+use crate::verify;
+
+/// startups allowed before a pending update is rolled back
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// describe an update binary, also the json manifest of an update channel
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct UpdateManifest {
+    pub version: String,
+    /// lowercase hex sha256 of the binary
+    pub sha256: String,
+    /// download url, the detached signature is at `{url}.sig`
+    #[serde(default)]
+    pub url: String,
+    /// versions older than this can't update to `version` directly
+    #[serde(default)]
+    pub min_version: Option<String>,
+}
+
+/// written beside the binary after swapping, removed once the new process is healthy
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq, Eq)]
+pub struct PendingUpdate {
+    pub version: String,
+    pub sha256: String,
+    /// startups of the new binary without `confirm_healthy`
+    pub attempts: u32,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupState {
+    /// no pending update
+    Confirmed,
+    /// call `confirm_healthy` once the process works
+    Pending(PendingUpdate),
+    /// the previous binary is restored, exit and let the service manager restart it
+    RolledBack(PendingUpdate),
+}
+
+/// replace a binary atomically, keep the previous one as `.bak` and roll back
+/// when the new binary keeps failing before it confirms healthy
+/// ```rust,no_run
+/// use awesome_operates::manage::{StartupState, UpdateManifest, Updater};
+///
+/// # async {
+/// let updater = Updater::current_exe().unwrap();
+/// // on startup, before doing anything else
+/// if let StartupState::RolledBack(_) = updater.startup_check().await.unwrap() {
+///     std::process::exit(1);
+/// }
+/// // after the process is known to work
+/// updater.confirm_healthy().await.unwrap();
+///
+/// // when an update is downloaded
+/// let manifest = UpdateManifest {
+///     version: "0.2.0".to_owned(),
+///     sha256: "...".to_owned(),
+///     ..Default::default()
+/// };
+/// updater.apply("/tmp/agent-0.2.0", &manifest).await.unwrap();
+/// # };
+/// ```
+#[derive(Debug, Clone)]
+pub struct Updater<R: CommandRunner = ShellRunner> {
+    target: PathBuf,
+    manager: ServiceManager<R>,
+    max_attempts: u32,
+}
+
+impl Updater {
+    pub fn new(target: impl AsRef<Path>) -> Self {
+        Self::with_manager(target, ServiceManager::default())
+    }
+
+    pub fn current_exe() -> Result<Self> {
+        Ok(Self::new(std::env::current_exe().context(CommonIoSnafu)?))
+    }
+}
+
+impl<R: CommandRunner> Updater<R> {
+    /// `manager` runs the `--version` check of update binaries
+    pub fn with_manager(target: impl AsRef<Path>, manager: ServiceManager<R>) -> Self {
+        Self {
+            target: target.as_ref().to_path_buf(),
+            manager,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn backup_path(&self) -> PathBuf {
+        self.sibling("bak")
+    }
+
+    pub fn pending_path(&self) -> PathBuf {
+        self.sibling("pending")
+    }
+
+    fn sibling(&self, extension: &str) -> PathBuf {
+        let mut filename = self.target.file_name().unwrap_or_default().to_os_string();
+        filename.push(format!(".{extension}"));
+        self.target.with_file_name(filename)
+    }
+
+    /// copy `update` beside the target, verify the copy by the manifest, the detached signature
+    /// and `--version`, then swap it with the target
+    pub async fn apply(&self, update: impl AsRef<Path>, manifest: &UpdateManifest) -> Result<()> {
+        let update = update.as_ref();
+        // copy into the target directory first, so the final rename is atomic,
+        // and verify the copy, so the checked bytes are the installed ones
+        let staged = self.sibling("new");
+        tokio::fs::copy(update, &staged)
+            .await
+            .context(CommonIoSnafu)?;
+        let actual = match self.verify_staged(&staged, update, manifest).await {
+            Ok(actual) => actual,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&staged).await;
+                return Err(e);
+            }
+        };
+        sync_path(&staged).await?;
+        if self.target.exists() {
+            let backup = self.backup_path();
+            let _ = tokio::fs::remove_file(&backup).await;
+            if tokio::fs::hard_link(&self.target, &backup).await.is_err() {
+                tokio::fs::copy(&self.target, &backup)
+                    .await
+                    .context(CommonIoSnafu)?;
+            }
+        }
+        self.write_pending(&PendingUpdate {
+            version: manifest.version.clone(),
+            sha256: actual,
+            attempts: 0,
+            updated_at: default_formatted_now(),
+        })
+        .await?;
+        if let Some(parent) = self.target.parent() {
+            sync_dir(parent).await?;
+        }
+        tokio::fs::rename(&staged, &self.target)
+            .await
+            .context(CommonIoSnafu)?;
+        if let Some(parent) = self.target.parent() {
+            sync_dir(parent).await?;
+        }
+        tracing::info!(
+            "update {} into version {}, previous one at {}",
+            self.target.display(),
+            manifest.version,
+            self.backup_path().display()
+        );
+        Ok(())
+    }
+
+    /// sha256 of `staged` when it matches the manifest, the signature is the one of `update`
+    async fn verify_staged(
+        &self,
+        staged: &Path,
+        update: &Path,
+        manifest: &UpdateManifest,
+    ) -> Result<String> {
+        let filepath = update.to_str().context(OptionNoneSnafu)?;
+        let data = tokio::fs::read(staged).await.context(CommonIoSnafu)?;
+        let actual = hex::encode(Sha256::digest(&data));
+        if !actual.eq_ignore_ascii_case(&manifest.sha256) {
+            tracing::error!("refuse update {filepath}, sha256 mismatch");
+            return ChecksumMismatchSnafu {
+                filepath,
+                expected: &manifest.sha256,
+                actual,
+            }
+            .fail();
+        }
+        verify::verify_update_data(update, &data).await?;
+        let staged_filepath = staged.to_str().context(OptionNoneSnafu)?;
+        if !self
+            .manager
+            .binary_filepath_execute_success(staged_filepath)
+            .await?
+        {
+            return BinaryCannotBeExecuteSnafu { filepath }.fail();
+        }
+        Ok(actual)
+    }
+
+    pub async fn pending(&self) -> Result<Option<PendingUpdate>> {
+        let path = self.pending_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = tokio::fs::read(path).await.context(CommonIoSnafu)?;
+        Ok(Some(
+            serde_json::from_slice(&content).context(SerdeJsonSnafu)?,
+        ))
+    }
+
+    /// count a startup of the pending update, roll back when it exceeds `max_attempts`
+    pub async fn startup_check(&self) -> Result<StartupState> {
+        let Some(mut pending) = self.pending().await? else {
+            return Ok(StartupState::Confirmed);
+        };
+        pending.attempts += 1;
+        if pending.attempts > self.max_attempts {
+            tracing::error!(
+                "update {} failed to confirm healthy after {} startups, roll back",
+                pending.version,
+                self.max_attempts
+            );
+            self.rollback().await?;
+            return Ok(StartupState::RolledBack(pending));
+        }
+        self.write_pending(&pending).await?;
+        Ok(StartupState::Pending(pending))
+    }
+
+    /// the new binary works, the backup is kept for manual rollback
+    pub async fn confirm_healthy(&self) -> Result<()> {
+        if let Some(pending) = self.pending().await? {
+            tracing::info!("update {} confirmed healthy", pending.version);
+            tokio::fs::remove_file(self.pending_path())
+                .await
+                .context(CommonIoSnafu)?;
+        }
+        Ok(())
+    }
+
+    /// restore the `.bak` binary and drop the pending state
+    /// without a backup the pending state is dropped too, so the next startup is not rolled back again
+    pub async fn rollback(&self) -> Result<()> {
+        let backup = self.backup_path();
+        let restored = if backup.exists() {
+            tokio::fs::rename(&backup, &self.target)
+                .await
+                .context(CommonIoSnafu)
+        } else {
+            BackupMissingSnafu {
+                filepath: backup.display().to_string(),
+            }
+            .fail()
+        };
+        let pending = self.pending_path();
+        if pending.exists() {
+            tokio::fs::remove_file(pending)
+                .await
+                .context(CommonIoSnafu)?;
+        }
+        restored?;
+        tracing::warn!("rollback {}", self.target.display());
+        Ok(())
+    }
+
+    async fn write_pending(&self, pending: &PendingUpdate) -> Result<()> {
+        let content = serde_json::to_vec_pretty(pending).context(SerdeJsonSnafu)?;
+        let path = self.pending_path();
+        let staged = self.sibling("pending.tmp");
+        tokio::fs::write(&staged, content)
+            .await
+            .context(CommonIoSnafu)?;
+        tokio::fs::rename(staged, path).await.context(CommonIoSnafu)
+    }
+}
+
+/// flush the file content to disk
+async fn sync_path(path: &Path) -> Result<()> {
+    tokio::fs::File::open(path)
+        .await
+        .context(CommonIoSnafu)?
+        .sync_all()
+        .await
+        .context(CommonIoSnafu)
+}
+
+/// flush the directory entries to disk, so a rename survives a crash, windows can't open directories
+async fn sync_dir(path: &Path) -> Result<()> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            sync_path(path).await
+        } else {
+            let _ = path;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::Signer;
+
+    use super::*;
+    use crate::helper::sha256_file;
+    use crate::manage::RecordingRunner;
+
+    #[tokio::test]
+    async fn apply_confirm_and_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let target = dir.join("agent");
+        let update = dir.join("agent-0.2.0");
+        tokio::fs::write(&target, "old").await.unwrap();
+        tokio::fs::write(&update, "new").await.unwrap();
+        let signature = verify::test_signing_key().sign(b"new");
+        tokio::fs::write(verify::signature_path(&update), signature.to_bytes())
+            .await
+            .unwrap();
+        let updater = Updater::with_manager(
+            &target,
+            ServiceManager::with_runner(RecordingRunner::default()),
+        )
+        .max_attempts(1);
+
+        let mut manifest = UpdateManifest {
+            version: "0.2.0".to_owned(),
+            sha256: "0".repeat(64),
+            ..Default::default()
+        };
+        assert!(updater.apply(&update, &manifest).await.is_err());
+        assert!(!updater.sibling("new").exists());
+        manifest.sha256 = sha256_file(&update).await.unwrap();
+        updater.apply(&update, &manifest).await.unwrap();
+        assert_eq!(tokio::fs::read_to_string(&target).await.unwrap(), "new");
+        assert_eq!(
+            tokio::fs::read_to_string(updater.backup_path())
+                .await
+                .unwrap(),
+            "old"
+        );
+
+        assert!(matches!(
+            updater.startup_check().await.unwrap(),
+            StartupState::Pending(PendingUpdate { attempts: 1, .. })
+        ));
+        assert!(matches!(
+            updater.startup_check().await.unwrap(),
+            StartupState::RolledBack(_)
+        ));
+        assert_eq!(tokio::fs::read_to_string(&target).await.unwrap(), "old");
+        assert_eq!(
+            updater.startup_check().await.unwrap(),
+            StartupState::Confirmed
+        );
+
+        tokio::fs::write(&target, "old").await.unwrap();
+        updater.apply(&update, &manifest).await.unwrap();
+        updater.confirm_healthy().await.unwrap();
+        assert_eq!(updater.pending().await.unwrap(), None);
+
+        updater.apply(&update, &manifest).await.unwrap();
+        tokio::fs::remove_file(updater.backup_path()).await.unwrap();
+        assert!(matches!(
+            updater.rollback().await,
+            Err(crate::error::AppError::BackupMissing { .. })
+        ));
+        assert_eq!(updater.pending().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,167 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use super::trigger::JobTrigger;
+use super::{Clock, JOB_TRACKER};
+use crate::graceful::shutdown_token;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// control a spawned job, dropping the handle keeps the job running
+#[derive(Clone)]
+pub struct JobHandle {
+    state: watch::Sender<JobState>,
+    trigger: Arc<dyn JobTrigger>,
+}
+
+impl JobHandle {
+    /// skip the fire times until `resume`, a running `f()` is not interrupted
+    pub fn pause(&self) {
+        self.transition(JobState::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.transition(JobState::Running);
+    }
+
+    /// stop the job forever, a running `f()` is not interrupted
+    pub fn cancel(&self) {
+        self.state.send_replace(JobState::Cancelled);
+    }
+
+    pub fn state(&self) -> JobState {
+        *self.state.borrow()
+    }
+
+    /// `None` when cancelled or the trigger will never fire again
+    pub fn next_run(&self) -> Option<DateTime<FixedOffset>> {
+        self.next_runs(1).pop()
+    }
+
+    pub fn next_runs(&self, count: usize) -> Vec<DateTime<FixedOffset>> {
+        if self.state().eq(&JobState::Cancelled) {
+            return vec![];
+        }
+        self.trigger.next_runs(count)
+    }
+
+    fn transition(&self, to: JobState) {
+        self.state.send_if_modified(|state| {
+            if (*state).eq(&JobState::Cancelled) || (*state).eq(&to) {
+                return false;
+            }
+            *state = to;
+            true
+        });
+    }
+}
+
+/// spawn `f` to run at every fire time of `trigger`
+/// the task sleeps until the next fire time instead of polling
+/// the job is cancelled when `graceful::shutdown_token()` is cancelled
+pub fn spawn_job<F, Res>(f: F, trigger: impl JobTrigger) -> JobHandle
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+{
+    spawn_job_in(f, trigger, shutdown_token(), &JOB_TRACKER)
+}
+
+/// same as `spawn_job`, but cancelled by `shutdown` and the runs are tracked by `tracker`
+/// `schedule::wait_jobs_finished` only waits jobs stopped by `graceful::shutdown_token()`,
+/// close and wait `tracker` instead after cancelling `shutdown`
+pub fn spawn_job_with_shutdown<F, Res>(
+    f: F,
+    trigger: impl JobTrigger,
+    shutdown: CancellationToken,
+    tracker: &TaskTracker,
+) -> JobHandle
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+{
+    spawn_job_in(f, trigger, shutdown, tracker)
+}
+
+/// the scheduling task and a running `f()` are tracked by `tracker`, `f()` is never interrupted
+pub(super) fn spawn_job_in<F, Res>(
+    mut f: F,
+    trigger: impl JobTrigger,
+    shutdown: CancellationToken,
+    tracker: &TaskTracker,
+) -> JobHandle
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+{
+    let (state, _) = watch::channel(JobState::Running);
+    let handle = JobHandle {
+        state,
+        trigger: Arc::new(trigger),
+    };
+    let trigger = handle.trigger.clone();
+    let sender = handle.state.clone();
+    let mut state = sender.subscribe();
+    // the task owns a sender, so dropping every handle doesn't stop the job
+    tracker.spawn(async move {
+        // the fire time just handled, the next one is strictly after it
+        // so an early timer wake up against the wall clock never fires twice
+        let mut last: Option<DateTime<Utc>> = None;
+        let clock = Clock::new();
+        loop {
+            let current = *state.borrow_and_update();
+            match current {
+                JobState::Cancelled => break,
+                JobState::Paused => {
+                    tokio::select! {
+                        changed = state.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                        }
+                        _ = shutdown.cancelled() => {
+                            sender.send_replace(JobState::Cancelled);
+                        }
+                    }
+                    continue;
+                }
+                JobState::Running => {}
+            }
+            let current_time = clock.now();
+            let after = last.map_or(current_time, |last| last.max(current_time));
+            let Some(next) = trigger.next_after(after) else {
+                tracing::info!("job trigger will never fire again, stop it");
+                break;
+            };
+            let next = next.with_timezone(&Utc);
+            tokio::select! {
+                _ = tokio::time::sleep_until(clock.instant_at(next)) => {
+                    last = Some(next);
+                    f().await
+                }
+                changed = state.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown.cancelled() => {
+                    tracing::info!("shutdown, stop scheduling job");
+                    sender.send_replace(JobState::Cancelled);
+                }
+            }
+        }
+    });
+    handle
+}
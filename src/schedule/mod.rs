@@ -0,0 +1,354 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::task::TaskTracker;
+
+pub use job::{spawn_job, spawn_job_with_shutdown, JobHandle, JobState};
+pub use registry::{JobOptions, JobRegistry, JobRun, JobStatus, OverlapPolicy, RunOutcome};
+pub use trigger::{CronSchedule, JobTrigger};
+
+use crate::error::Result;
+use crate::graceful::shutdown_token;
+
+mod job;
+mod registry;
+mod trigger;
+
+/// track scheduling tasks and runs stopped by `graceful::shutdown_token()`, used by `wait_jobs_finished`
+static JOB_TRACKER: Lazy<TaskTracker> = Lazy::new(TaskTracker::new);
+
+/// wall clock followed with the tokio clock from the moment it's created,
+/// so fire times are slept with `tokio::time::sleep_until` and `tokio::time::pause` works on them
+#[derive(Clone, Copy)]
+struct Clock {
+    wall: DateTime<Utc>,
+    instant: Instant,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Self {
+            wall: Utc::now(),
+            instant: Instant::now(),
+        }
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.wall + chrono::Duration::from_std(self.instant.elapsed()).unwrap_or_default()
+    }
+
+    /// the tokio instant of `time`, a past time is the creation instant
+    fn instant_at(&self, time: DateTime<Utc>) -> Instant {
+        self.instant + (time - self.wall).to_std().unwrap_or_default()
+    }
+}
+
+/// wait for in-flight runs of the jobs stopped by `graceful::shutdown_token()` after shutdown
+/// return `false` when `deadline` exceeded
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use awesome_operates::graceful::shutdown_signal;
+/// use awesome_operates::schedule::wait_jobs_finished;
+///
+/// # async {
+/// shutdown_signal().await;
+/// if !wait_jobs_finished(Duration::from_secs(30)).await {
+///     tracing::warn!("some jobs are still running");
+/// }
+/// # };
+/// ```
+pub async fn wait_jobs_finished(deadline: Duration) -> bool {
+    wait_tracker(&JOB_TRACKER, deadline).await
+}
+
+async fn wait_tracker(tracker: &TaskTracker, deadline: Duration) -> bool {
+    tracker.close();
+    let finished = tokio::time::timeout(deadline, tracker.wait()).await.is_ok();
+    if !finished {
+        tracing::warn!(
+            "{} scheduled tasks still running after {deadline:?}",
+            tracker.len()
+        );
+    }
+    finished
+}
+
+/// run with cron expression in local time zone, seconds field is required
+/// ```rust,no_run
+/// use awesome_operates::schedule::run_with_cron;
+///
+/// async fn hello() {
+///     println!("aaa");
+/// }
+///
+/// # async {
+/// // every 5 seconds
+/// let handle = run_with_cron(hello, "*/5 * * * * *").unwrap();
+/// println!("next run at {:?}", handle.next_run());
+/// handle.pause();
+/// handle.resume();
+/// handle.cancel();
+/// # };
+/// ```
+pub fn run_with_cron<F, Res>(f: F, expression: &str) -> Result<JobHandle>
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+{
+    run_with_cron_tz(f, expression, chrono::Local)
+}
+
+/// same as `run_with_cron` but the expression is evaluated in `timezone`
+pub fn run_with_cron_tz<F, Res, Z>(f: F, expression: &str, timezone: Z) -> Result<JobHandle>
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+    Z: TimeZone + Send + Sync + 'static,
+    Z::Offset: Send + Sync,
+{
+    Ok(spawn_job(f, CronSchedule::new(expression, timezone)?))
+}
+
+/// run with interval as second as unit
+/// stop after `graceful::shutdown_token()` is cancelled, a running `f()` is not interrupted
+/// ```rust,no_run
+/// use std::sync::Once;
+///
+/// use awesome_operates::schedule::run_with_interval;
+///
+/// static START: Once = Once::new();
+///
+/// async fn hello() {
+///     println!("aaa");
+/// }
+///
+/// START.call_once(|| {
+///  #   run_with_interval(hello, 3);
+/// });
+/// ```
+pub fn run_with_interval<F, Res>(mut f: F, interval: u64)
+where
+    F: 'static + FnMut() -> Res + Send,
+    Res: 'static + Future<Output = ()> + Send,
+{
+    let shutdown = shutdown_token();
+    let start = Instant::now() + until_next_generate(interval, Utc::now());
+    JOB_TRACKER.spawn(async move {
+        let mut interval_task = tokio::time::interval_at(start, Duration::from_secs(interval));
+        interval_task.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                _ = interval_task.tick() => f().await,
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+}
+
+/// don't change this logic
+/// the first run is at the begin of a second which is a multiple of `interval`
+fn until_next_generate(interval: u64, now: DateTime<Utc>) -> Duration {
+    let period = interval * 1000;
+    let elapsed = now.timestamp_millis() as u64 % period;
+    Duration::from_millis((period - elapsed) % period)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    /// fire every 100 milliseconds
+    struct FastTrigger;
+
+    impl JobTrigger for FastTrigger {
+        fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<FixedOffset>> {
+            Some((after + chrono::Duration::milliseconds(100)).fixed_offset())
+        }
+    }
+
+    /// advance the paused clock step by step, so the spawned jobs catch up with every fire time
+    async fn advance(duration: Duration) {
+        let step = Duration::from_millis(10);
+        for _ in 0..duration.as_millis() / step.as_millis() {
+            tokio::time::advance(step).await;
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn registry_skip_and_timeout() {
+        let registry = JobRegistry::default();
+        let options = JobOptions {
+            timeout: Some(Duration::from_millis(250)),
+            ..Default::default()
+        };
+        registry.register(
+            "slow",
+            || tokio::time::sleep(Duration::from_secs(10)),
+            FastTrigger,
+            options,
+        );
+        advance(Duration::from_millis(700)).await;
+        let status = registry.status("slow").unwrap();
+        assert_eq!(status.state, JobState::Running);
+        assert!(status
+            .history
+            .iter()
+            .any(|run| run.outcome.eq(&RunOutcome::Skipped)));
+        assert!(status
+            .history
+            .iter()
+            .any(|run| run.outcome.eq(&RunOutcome::Timeout)));
+        assert!(registry.remove("slow"));
+        assert!(registry.status("slow").is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn registry_queue_depth() {
+        let registry = JobRegistry::default();
+        let options = JobOptions {
+            overlap: OverlapPolicy::Queue,
+            queue_depth: 1,
+            ..Default::default()
+        };
+        registry.register(
+            "queued",
+            || tokio::time::sleep(Duration::from_secs(10)),
+            FastTrigger,
+            options,
+        );
+        tokio::time::sleep(Duration::from_millis(550)).await;
+        let status = registry.status("queued").unwrap();
+        // one running and one waiting, the others are skipped
+        assert_eq!(status.running, 2);
+        assert_eq!(
+            status
+                .history
+                .iter()
+                .filter(|run| run.outcome.eq(&RunOutcome::Skipped))
+                .count(),
+            3
+        );
+        registry.remove("queued");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn registry_panic_capture() {
+        let registry = JobRegistry::default();
+        registry.register(
+            "restart",
+            || async { panic!("boom") },
+            FastTrigger,
+            JobOptions::default(),
+        );
+        let options = JobOptions {
+            restart_on_panic: false,
+            ..Default::default()
+        };
+        registry.register("once", || async { panic!("boom") }, FastTrigger, options);
+        advance(Duration::from_millis(450)).await;
+
+        let restart = registry.status("restart").unwrap();
+        assert_eq!(restart.state, JobState::Running);
+        assert!(restart.history.len() > 1);
+        assert_eq!(
+            restart.history[0].outcome,
+            RunOutcome::Panicked("boom".to_owned())
+        );
+
+        let once = registry.status("once").unwrap();
+        assert_eq!(once.state, JobState::Cancelled);
+        assert_eq!(once.history.len(), 1);
+    }
+
+    #[test]
+    fn cron_next_after_with_timezone() {
+        let offset = FixedOffset::east_opt(8 * 3600).unwrap();
+        let schedule = CronSchedule::new("0 30 8 * * *", offset).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let next = schedule.next_after(after).unwrap();
+        assert_eq!(next.to_rfc3339(), "2024-01-01T08:30:00+08:00");
+        assert_eq!(
+            next.with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 30, 0).unwrap()
+        );
+        assert!(CronSchedule::new("not a cron", offset).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cron_pause_resume_cancel() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let handle = run_with_cron(
+            move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+            "* * * * * *",
+        )
+        .unwrap();
+        assert_eq!(handle.next_runs(2).len(), 2);
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        // fired once at every second boundary, never twice
+        assert!((2..=3).contains(&count.load(Ordering::SeqCst)));
+
+        handle.pause();
+        assert_eq!(handle.state(), JobState::Paused);
+        let paused = count.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(count.load(Ordering::SeqCst), paused);
+
+        handle.resume();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(count.load(Ordering::SeqCst) > paused);
+
+        handle.cancel();
+        handle.resume();
+        assert_eq!(handle.state(), JobState::Cancelled);
+        assert!(handle.next_run().is_none());
+    }
+
+    #[test]
+    fn interval_begin_at_multiple() {
+        let at = |millis| Utc.timestamp_millis_opt(millis).unwrap();
+        assert_eq!(until_next_generate(3, at(9_000)), Duration::ZERO);
+        assert_eq!(
+            until_next_generate(3, at(9_250)),
+            Duration::from_millis(2_750)
+        );
+        assert_eq!(until_next_generate(3, at(11_999)), Duration::from_millis(1));
+    }
+
+    #[tokio::test]
+    async fn registry_stop_on_shutdown() {
+        let shutdown = tokio_util::sync::CancellationToken::new();
+        let registry = JobRegistry::with_shutdown(shutdown.clone());
+        let handle = registry.register(
+            "graceful",
+            || tokio::time::sleep(Duration::from_millis(300)),
+            FastTrigger,
+            JobOptions::default(),
+        );
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        shutdown.cancel();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(handle.state(), JobState::Cancelled);
+        assert_eq!(registry.status("graceful").unwrap().running, 1);
+
+        assert!(registry.wait_finished(Duration::from_secs(1)).await);
+        let status = registry.status("graceful").unwrap();
+        assert_eq!(status.running, 0);
+        assert_eq!(status.history.last().unwrap().outcome, RunOutcome::Success);
+    }
+}
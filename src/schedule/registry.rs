@@ -0,0 +1,381 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use super::job::{spawn_job_in, JobHandle, JobState};
+use super::trigger::{CronSchedule, JobTrigger};
+use super::{wait_tracker, JOB_TRACKER};
+use crate::error::Result;
+use crate::graceful::shutdown_token;
+use crate::helper::default_formatted_now;
+
+/// what to do when a fire time comes while the previous run is not finished
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    /// don't start, the fire time is recorded as `RunOutcome::Skipped`
+    #[default]
+    Skip,
+    /// wait until the previous runs are finished, one by one
+    /// at most `JobOptions::queue_depth` runs wait, later fire times are skipped
+    Queue,
+    /// start anyway
+    Concurrent,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobOptions {
+    pub overlap: OverlapPolicy,
+    /// max count of runs waiting with `OverlapPolicy::Queue`
+    pub queue_depth: usize,
+    /// the run is aborted and recorded as `RunOutcome::Timeout` after this
+    pub timeout: Option<Duration>,
+    /// max count of recent runs kept
+    pub history_size: usize,
+    /// keep firing after a run panicked, or cancel the job
+    pub restart_on_panic: bool,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        Self {
+            overlap: OverlapPolicy::Skip,
+            queue_depth: 1,
+            timeout: None,
+            history_size: 20,
+            restart_on_panic: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "status", content = "message")]
+pub enum RunOutcome {
+    Success,
+    Timeout,
+    Panicked(String),
+    /// the run task was cancelled, e.g. the runtime is shutting down
+    Cancelled,
+    Skipped,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JobRun {
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub outcome: RunOutcome,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JobStatus {
+    pub name: String,
+    pub state: JobState,
+    /// count of runs not finished
+    pub running: usize,
+    /// rfc3339 format
+    pub next_run: Option<String>,
+    /// oldest first
+    pub history: Vec<JobRun>,
+}
+
+struct RegisteredJob {
+    handle: JobHandle,
+    running: Arc<AtomicUsize>,
+    history: Arc<Mutex<VecDeque<JobRun>>>,
+}
+
+/// named jobs with overlap policy, timeout, panic capture and run history
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use awesome_operates::schedule::{JobOptions, JobRegistry, OverlapPolicy};
+///
+/// async fn sync_data() {}
+///
+/// # async {
+/// let registry = JobRegistry::default();
+/// let options = JobOptions {
+///     overlap: OverlapPolicy::Skip,
+///     timeout: Some(Duration::from_secs(30)),
+///     ..Default::default()
+/// };
+/// registry.register_cron("sync", sync_data, "0 */5 * * * *", options).unwrap();
+/// println!("{:?}", registry.status("sync"));
+/// # };
+/// ```
+#[derive(Clone)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, RegisteredJob>>>,
+    shutdown: CancellationToken,
+    tracker: TaskTracker,
+}
+
+/// stop with `graceful::shutdown_token()`, in-flight runs are waited by `schedule::wait_jobs_finished`
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self {
+            jobs: Default::default(),
+            shutdown: shutdown_token(),
+            tracker: JOB_TRACKER.clone(),
+        }
+    }
+}
+
+impl JobRegistry {
+    /// jobs stop starting new runs after `shutdown` is cancelled
+    /// in-flight runs are waited by `JobRegistry::wait_finished`
+    pub fn with_shutdown(shutdown: CancellationToken) -> Self {
+        Self {
+            jobs: Default::default(),
+            shutdown,
+            tracker: TaskTracker::new(),
+        }
+    }
+
+    /// wait for in-flight runs of the jobs after shutdown
+    /// return `false` when `deadline` exceeded
+    pub async fn wait_finished(&self, deadline: Duration) -> bool {
+        wait_tracker(&self.tracker, deadline).await
+    }
+
+    /// register a job in local time zone, see `JobRegistry::register`
+    pub fn register_cron<F, Res>(
+        &self,
+        name: &str,
+        f: F,
+        expression: &str,
+        options: JobOptions,
+    ) -> Result<JobHandle>
+    where
+        F: 'static + FnMut() -> Res + Send,
+        Res: 'static + Future<Output = ()> + Send,
+    {
+        let schedule = CronSchedule::new(expression, chrono::Local)?;
+        Ok(self.register(name, f, schedule, options))
+    }
+
+    /// register and start a job, the job with the same name is cancelled and replaced
+    pub fn register<F, Res>(
+        &self,
+        name: &str,
+        mut f: F,
+        trigger: impl JobTrigger,
+        options: JobOptions,
+    ) -> JobHandle
+    where
+        F: 'static + FnMut() -> Res + Send,
+        Res: 'static + Future<Output = ()> + Send,
+    {
+        let running = Arc::new(AtomicUsize::new(0));
+        let history = Arc::new(Mutex::new(VecDeque::with_capacity(options.history_size)));
+        let queue = Arc::new(Semaphore::new(1));
+        // set after the job is spawned, only when `restart_on_panic` is false
+        let cancel_on_panic = Arc::new(OnceCell::<JobHandle>::new());
+        let (job_running, job_history) = (running.clone(), history.clone());
+        let job_cancel_on_panic = cancel_on_panic.clone();
+        let job_name = name.to_owned();
+        let job_shutdown = self.shutdown.clone();
+        let tracker = self.tracker.clone();
+        let handle = spawn_job_in(
+            move || {
+                let recorder = RunRecorder {
+                    name: job_name.clone(),
+                    running: job_running.clone(),
+                    history: job_history.clone(),
+                    history_size: options.history_size,
+                    cancel_on_panic: job_cancel_on_panic.clone(),
+                    shutdown: job_shutdown.clone(),
+                };
+                let skip = match options.overlap {
+                    OverlapPolicy::Skip => recorder.running() > 0,
+                    // one running and `queue_depth` waiting
+                    OverlapPolicy::Queue => recorder.running() > options.queue_depth,
+                    OverlapPolicy::Concurrent => false,
+                };
+                if skip {
+                    tracing::warn!("job `{job_name}` is still running, skip this time");
+                    recorder.record(default_formatted_now(), Instant::now(), RunOutcome::Skipped);
+                    return std::future::ready(());
+                }
+                let queue = match options.overlap {
+                    OverlapPolicy::Queue => Some(queue.clone()),
+                    _ => None,
+                };
+                recorder.running.fetch_add(1, Ordering::SeqCst);
+                tracker.spawn(recorder.run(f(), queue, options.timeout));
+                std::future::ready(())
+            },
+            trigger,
+            self.shutdown.clone(),
+            &self.tracker,
+        );
+        if !options.restart_on_panic {
+            let _ = cancel_on_panic.set(handle.clone());
+        }
+        let job = RegisteredJob {
+            handle: handle.clone(),
+            running,
+            history,
+        };
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(previous) = jobs.insert(name.to_owned(), job) {
+                tracing::info!("job `{name}` is replaced");
+                previous.handle.cancel();
+            }
+        }
+        handle
+    }
+
+    pub fn get(&self, name: &str) -> Option<JobHandle> {
+        Some(self.jobs.lock().ok()?.get(name)?.handle.clone())
+    }
+
+    /// cancel and remove the job
+    pub fn remove(&self, name: &str) -> bool {
+        let Some(job) = self.jobs.lock().ok().and_then(|mut jobs| jobs.remove(name)) else {
+            return false;
+        };
+        job.handle.cancel();
+        true
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .jobs
+            .lock()
+            .map(|jobs| jobs.keys().cloned().collect::<Vec<String>>())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// recent runs, oldest first
+    pub fn history(&self, name: &str) -> Option<Vec<JobRun>> {
+        let jobs = self.jobs.lock().ok()?;
+        let history = jobs.get(name)?.history.lock().ok()?;
+        Some(history.iter().cloned().collect())
+    }
+
+    pub fn status(&self, name: &str) -> Option<JobStatus> {
+        let jobs = self.jobs.lock().ok()?;
+        let job = jobs.get(name)?;
+        Some(JobStatus {
+            name: name.to_owned(),
+            state: job.handle.state(),
+            running: job.running.load(Ordering::SeqCst),
+            next_run: job.handle.next_run().map(|next| next.to_rfc3339()),
+            history: job
+                .history
+                .lock()
+                .map(|history| history.iter().cloned().collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// status of all jobs sorted by name
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.names()
+            .iter()
+            .filter_map(|name| self.status(name))
+            .collect()
+    }
+}
+
+struct RunRecorder {
+    name: String,
+    running: Arc<AtomicUsize>,
+    history: Arc<Mutex<VecDeque<JobRun>>>,
+    history_size: usize,
+    cancel_on_panic: Arc<OnceCell<JobHandle>>,
+    shutdown: CancellationToken,
+}
+
+impl RunRecorder {
+    fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    async fn run<Res>(self, run: Res, queue: Option<Arc<Semaphore>>, timeout: Option<Duration>)
+    where
+        Res: 'static + Future<Output = ()> + Send,
+    {
+        let _permit = match &queue {
+            Some(queue) => queue.acquire().await.ok(),
+            None => None,
+        };
+        let started_at = default_formatted_now();
+        let start = Instant::now();
+        if queue.is_some() && self.shutdown.is_cancelled() {
+            tracing::info!("shutdown, skip queued run of job `{}`", self.name);
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.record(started_at, start, RunOutcome::Skipped);
+            return;
+        }
+        let mut task = tokio::spawn(run);
+        let joined = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut task).await.ok(),
+            None => Some((&mut task).await),
+        };
+        let outcome = match joined {
+            None => {
+                task.abort();
+                tracing::warn!("job `{}` timeout, abort it", self.name);
+                RunOutcome::Timeout
+            }
+            Some(Ok(_)) => RunOutcome::Success,
+            Some(Err(e)) if e.is_panic() => {
+                let message = panic_message(e.into_panic());
+                tracing::error!("job `{}` panicked with `{message}`", self.name);
+                if let Some(handle) = self.cancel_on_panic.get() {
+                    tracing::warn!(
+                        "cancel job `{}` because restart_on_panic is false",
+                        self.name
+                    );
+                    handle.cancel();
+                }
+                RunOutcome::Panicked(message)
+            }
+            Some(Err(_)) => {
+                tracing::warn!("job `{}` run is cancelled", self.name);
+                RunOutcome::Cancelled
+            }
+        };
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        self.record(started_at, start, outcome);
+    }
+
+    fn record(&self, started_at: String, start: Instant, outcome: RunOutcome) {
+        let Ok(mut history) = self.history.lock() else {
+            return;
+        };
+        if history.len() >= self.history_size {
+            history.pop_front();
+        }
+        if self.history_size > 0 {
+            history.push_back(JobRun {
+                started_at,
+                duration_ms: start.elapsed().as_millis() as u64,
+                outcome,
+            });
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
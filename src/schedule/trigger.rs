@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use snafu::ResultExt;
+
+use crate::error::{CronParseSnafu, Result};
+
+/// decide when a job should run
+pub trait JobTrigger: Send + Sync + 'static {
+    /// the first fire time strictly after `after`, `None` means never fire again
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<FixedOffset>>;
+
+    /// the next `count` fire times after now
+    fn next_runs(&self, count: usize) -> Vec<DateTime<FixedOffset>> {
+        let mut runs = Vec::with_capacity(count);
+        let mut after = Utc::now();
+        while runs.len() < count {
+            let Some(next) = self.next_after(after) else {
+                break;
+            };
+            after = next.with_timezone(&Utc);
+            runs.push(next);
+        }
+        runs
+    }
+}
+
+/// cron expression with seconds field, evaluated in `timezone`
+/// `sec min hour day-of-month month day-of-week [year]`
+/// ```rust
+/// use awesome_operates::schedule::{CronSchedule, JobTrigger};
+///
+/// // at 08:30:00 every workday in UTC+8
+/// let offset = chrono::FixedOffset::east_opt(8 * 3600).unwrap();
+/// let schedule = CronSchedule::new("0 30 8 * * Mon-Fri", offset).unwrap();
+/// println!("{:?}", schedule.next_runs(3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CronSchedule<Z: TimeZone> {
+    schedule: cron::Schedule,
+    timezone: Z,
+}
+
+impl<Z: TimeZone> CronSchedule<Z> {
+    pub fn new(expression: &str, timezone: Z) -> Result<Self> {
+        Ok(Self {
+            schedule: cron::Schedule::from_str(expression).context(CronParseSnafu {
+                expression: expression.to_owned(),
+            })?,
+            timezone,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        self.schedule.source()
+    }
+}
+
+impl<Z> JobTrigger for CronSchedule<Z>
+where
+    Z: TimeZone + Send + Sync + 'static,
+    Z::Offset: Send + Sync,
+{
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<FixedOffset>> {
+        self.schedule
+            .after(&after.with_timezone(&self.timezone))
+            .next()
+            .map(|next| next.fixed_offset())
+    }
+}
@@ -0,0 +1,414 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use axum::body::Body;
+use http::header::{
+    ACCEPT_ENCODING, ACCEPT_RANGES, ALLOW, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+    CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, VARY,
+};
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use rust_embed::RustEmbed;
+use sha2::{Digest, Sha256};
+use tower::Service;
+
+/// precompressed variants looked up as `{path}{extension}`, in preference order
+const ENCODINGS: [(&str, &str); 3] = [("br", ".br"), ("zstd", ".zst"), ("gzip", ".gz")];
+
+/// generated content and its sha256
+type GeneratedFiles = HashMap<String, (Cow<'static, [u8]>, [u8; 32])>;
+
+struct Asset {
+    data: Cow<'static, [u8]>,
+    hash: [u8; 32],
+    last_modified: Option<u64>,
+    mimetype: String,
+}
+
+/// serve a `RustEmbed` straight from memory, a drop-in replacement for `ServeDir`
+/// with ETag from the embedded sha256, Last-Modified, single Range requests
+/// and precompressed `.br`, `.zst`, `.gz` variants embedded beside the files
+/// ```rust,no_run
+/// use axum::Router;
+/// use awesome_operates::embed::Asset;
+/// use awesome_operates::server::ServeEmbed;
+///
+/// let app: Router = Router::new().nest_service(
+///     "/swagger/",
+///     ServeEmbed::<Asset>::new()
+///         .prefix("embed_files/swagger/")
+///         .with_file("index.html", "<html></html>"),
+/// );
+/// ```
+pub struct ServeEmbed<A> {
+    prefix: String,
+    index: String,
+    files: Arc<GeneratedFiles>,
+    _asset: PhantomData<fn() -> A>,
+}
+
+impl<A> Clone for ServeEmbed<A> {
+    fn clone(&self) -> Self {
+        Self {
+            prefix: self.prefix.clone(),
+            index: self.index.clone(),
+            files: self.files.clone(),
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<A: RustEmbed> Default for ServeEmbed<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: RustEmbed> ServeEmbed<A> {
+    pub fn new() -> Self {
+        Self {
+            prefix: String::new(),
+            index: "index.html".to_owned(),
+            files: Default::default(),
+            _asset: PhantomData,
+        }
+    }
+
+    /// prepended to the request path, like `embed_files/swagger/`
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// served for paths end with `/`
+    pub fn index_file(mut self, index: impl Into<String>) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// serve generated content at `path`, takes precedence over the embedded files
+    pub fn with_file(mut self, path: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        let content = content.into();
+        let hash = Sha256::digest(&content).into();
+        Arc::make_mut(&mut self.files).insert(path.into(), (Cow::Owned(content), hash));
+        self
+    }
+
+    fn get(&self, path: &str) -> Option<Asset> {
+        if let Some((data, hash)) = self.files.get(path) {
+            return Some(Asset {
+                data: data.clone(),
+                hash: *hash,
+                last_modified: None,
+                mimetype: mime_guess::from_path(path)
+                    .first_or_octet_stream()
+                    .to_string(),
+            });
+        }
+        let file = A::get(&format!("{}{path}", self.prefix))?;
+        Some(Asset {
+            hash: file.metadata.sha256_hash(),
+            last_modified: file.metadata.last_modified(),
+            mimetype: file.metadata.mimetype().to_owned(),
+            data: file.data,
+        })
+    }
+
+    /// respond `request` without the body, so it can be used outside tower
+    pub fn response<B>(&self, request: &Request<B>) -> Response<Body> {
+        if request.method().ne(&Method::GET) && request.method().ne(&Method::HEAD) {
+            let mut response = status_response(StatusCode::METHOD_NOT_ALLOWED);
+            response
+                .headers_mut()
+                .insert(ALLOW, http::HeaderValue::from_static("GET, HEAD"));
+            return response;
+        }
+        let decoded =
+            percent_encoding::percent_decode_str(request.uri().path()).decode_utf8_lossy();
+        let mut path = decoded.trim_start_matches('/').to_owned();
+        if path.is_empty() || path.ends_with('/') {
+            path.push_str(&self.index);
+        }
+        if path.split('/').any(|segment| segment.eq("..")) {
+            return status_response(StatusCode::NOT_FOUND);
+        }
+        let Some(asset) = self.get(&path) else {
+            return status_response(StatusCode::NOT_FOUND);
+        };
+        let headers = request.headers();
+        let variants = ENCODINGS
+            .iter()
+            .filter_map(|(encoding, extension)| {
+                self.get(&format!("{path}{extension}"))
+                    .map(|variant| (*encoding, variant))
+            })
+            .collect::<Vec<(&str, Asset)>>();
+        let vary = !variants.is_empty();
+        let accepted = accepted_encodings(headers);
+        // a precompressed variant is its own representation, with its own ETag and ranges
+        let (encoding, data, hash) = match variants
+            .into_iter()
+            .find(|(encoding, _)| accepted.contains(encoding))
+        {
+            Some((encoding, variant)) => (Some(encoding), variant.data, variant.hash),
+            None => (None, asset.data, asset.hash),
+        };
+        let etag = format!("\"{}\"", hex::encode(hash));
+        let mut builder = Response::builder()
+            .header(CONTENT_TYPE, &asset.mimetype)
+            .header(ETAG, &etag)
+            .header(ACCEPT_RANGES, "bytes");
+        if vary {
+            builder = builder.header(VARY, ACCEPT_ENCODING.as_str());
+        }
+        if let Some(encoding) = encoding {
+            builder = builder.header(CONTENT_ENCODING, encoding);
+        }
+        if let Some(last_modified) = asset.last_modified {
+            let time = SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified);
+            builder = builder.header(LAST_MODIFIED, httpdate::fmt_http_date(time));
+        }
+        if not_modified(headers, &etag, asset.last_modified) {
+            return builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .unwrap_or_default();
+        }
+
+        let is_head = request.method().eq(&Method::HEAD);
+        let length = data.len() as u64;
+        let range = headers
+            .get(http::header::RANGE)
+            .and_then(|value| value.to_str().ok())
+            // multiple ranges are served as a full response
+            .filter(|value| !value.contains(',') && if_range_matches(headers, &etag))
+            .map(|value| parse_range(value, length));
+        match range {
+            Some(Some((start, end))) => builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_RANGE, format!("bytes {start}-{end}/{length}"))
+                .header(CONTENT_LENGTH, end - start + 1)
+                .body(body(
+                    Cow::Owned(data[start as usize..=end as usize].to_vec()),
+                    is_head,
+                ))
+                .unwrap_or_default(),
+            Some(None) => builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{length}"))
+                .body(Body::empty())
+                .unwrap_or_default(),
+            None => builder
+                .header(CONTENT_LENGTH, length)
+                .body(body(data, is_head))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl<A: RustEmbed, B> Service<Request<B>> for ServeEmbed<A> {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        ready(Ok(self.response(&request)))
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap_or_default()
+}
+
+fn body(data: Cow<'static, [u8]>, is_head: bool) -> Body {
+    if is_head {
+        Body::empty()
+    } else {
+        Body::from(data)
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: http::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<u64>) -> bool {
+    if let Some(if_none_match) = header_str(headers, IF_NONE_MATCH) {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag.eq("*") || tag.eq(etag));
+    }
+    let since =
+        header_str(headers, IF_MODIFIED_SINCE).and_then(|v| httpdate::parse_http_date(v).ok());
+    match (since, last_modified) {
+        (Some(since), Some(last_modified)) => {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified) <= since
+        }
+        _ => false,
+    }
+}
+
+/// a range applies when `If-Range` is absent or matches the ETag
+fn if_range_matches(headers: &HeaderMap, etag: &str) -> bool {
+    header_str(headers, IF_RANGE).is_none_or(|if_range| if_range.eq(etag))
+}
+
+fn accepted_encodings(headers: &HeaderMap) -> Vec<&str> {
+    header_str(headers, ACCEPT_ENCODING)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(|part| part.trim());
+            let encoding = parts.next()?;
+            let disabled = parts.any(|part| part.eq("q=0") || part.eq("q=0.0"));
+            (!encoding.is_empty() && !disabled).then_some(encoding)
+        })
+        .collect()
+}
+
+/// inclusive `(start, end)` of a single `bytes=` range, `None` when unsatisfiable
+fn parse_range(value: &str, length: u64) -> Option<(u64, u64)> {
+    let spec = value.strip_prefix("bytes=")?.trim();
+    if length == 0 {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>().ok()?.min(length);
+            (length.checked_sub(suffix)?, length - 1)
+        }
+        (start, "") => (start.parse::<u64>().ok()?, length - 1),
+        (start, end) => (
+            start.parse::<u64>().ok()?,
+            end.parse::<u64>().ok()?.min(length - 1),
+        ),
+    };
+    (start <= end && start < length).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::RANGE;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(RustEmbed)]
+    #[folder = "src/test_files/embed/"]
+    struct TestAsset;
+
+    fn request(path: &str, headers: &[(http::HeaderName, &str)]) -> Request<Body> {
+        let mut builder = Request::get(path);
+        for (name, value) in headers {
+            builder = builder.header(name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    async fn body_bytes(response: Response<Body>) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn serve_from_memory() {
+        let service = ServeEmbed::<TestAsset>::new().with_file("index.html", "<html></html>");
+        let response = service
+            .clone()
+            .oneshot(request("/hello.txt", &[]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(response.headers()[VARY], "accept-encoding");
+        let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+        assert_eq!(body_bytes(response).await, b"hello embedded assets\n");
+
+        let response = service.response(&request("/hello.txt", &[(IF_NONE_MATCH, &etag)]));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = service.response(&request("/hello.txt", &[(RANGE, "bytes=6-13")]));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 6-13/22");
+        assert_eq!(body_bytes(response).await, b"embedded");
+        let response = service.response(&request("/hello.txt", &[(RANGE, "bytes=100-")]));
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let gzip = (ACCEPT_ENCODING, "br;q=0, gzip, deflate");
+        let response = service.response(&request("/hello.txt", std::slice::from_ref(&gzip)));
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        let gzip_etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+        assert_ne!(gzip_etag, etag);
+        let gzip_data = TestAsset::get("hello.txt.gz").unwrap().data.to_vec();
+        assert_eq!(body_bytes(response).await, gzip_data);
+
+        let response = service.response(&request(
+            "/hello.txt",
+            &[gzip.clone(), (IF_NONE_MATCH, &gzip_etag)],
+        ));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[VARY], "accept-encoding");
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        let response = service.response(&request("/hello.txt", &[(IF_NONE_MATCH, &gzip_etag)]));
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service.response(&request(
+            "/hello.txt",
+            &[gzip.clone(), (RANGE, "bytes=0-1"), (IF_RANGE, &gzip_etag)],
+        ));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[CONTENT_RANGE],
+            format!("bytes 0-1/{}", gzip_data.len())
+        );
+        assert_eq!(body_bytes(response).await, gzip_data[..2]);
+        let response = service.response(&request(
+            "/hello.txt",
+            &[gzip, (RANGE, "bytes=0-1"), (IF_RANGE, &etag)],
+        ));
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service.response(&request("/", &[]));
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html");
+        assert_eq!(body_bytes(response).await, b"<html></html>");
+        let response = service.response(&request("/missing.txt", &[]));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        for method in [Method::POST, Method::PUT, Method::DELETE] {
+            let request = Request::builder()
+                .method(method)
+                .uri("/hello.txt")
+                .body(Body::empty())
+                .unwrap();
+            let response = service.response(&request);
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(response.headers()[ALLOW], "GET, HEAD");
+        }
+    }
+
+    #[test]
+    fn parse_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some((0, 9)));
+        assert_eq!(parse_range("bytes=90-", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=-10", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=50-200", 100), Some((50, 99)));
+        assert_eq!(parse_range("bytes=100-", 100), None);
+        assert_eq!(parse_range("bytes=9-1", 100), None);
+    }
+}
@@ -1,7 +1,11 @@
 use tower_http::services::ServeDir;
 
+pub use assets::ServeEmbed;
+
 use crate::compress::pre_compress_dir;
 
+mod assets;
+
 pub async fn server_dir(dir_path: &str) -> ServeDir {
     let dir_path_clone = dir_path.to_owned();
     tokio::task::spawn_blocking(move || {
@@ -52,7 +52,6 @@ use crate::error::{CommonIoSnafu, Result};
 ///}
 /// ```
 /// finally, you can visit at browser at http://127.0.0.1:3000/docs/ for your swagger
-
 pub struct InitSwagger {
     file_prefix: String,
     pub js_filename: String,
@@ -114,8 +113,9 @@ impl InitSwagger {
         Ok(())
     }
 
-    pub async fn rewrite_swagger_index_html(&self) -> Result<()> {
-        let index_html = format!(
+    /// content of the index html, can be served from memory by `server::ServeEmbed::with_file`
+    pub fn index_html(&self) -> String {
+        format!(
             r#"<!-- HTML for static distribution bundle build -->
 <!DOCTYPE html>
 <html lang="en">
@@ -137,19 +137,23 @@ impl InitSwagger {
 </html>
 "#,
             self.js_filename
-        );
+        )
+    }
+
+    pub async fn rewrite_swagger_index_html(&self) -> Result<()> {
         tracing::info!(
             "write swagger index at path: {}",
             self.index_html_filepath()
         );
-        tokio::fs::write(&self.index_html_filepath(), index_html)
+        tokio::fs::write(&self.index_html_filepath(), self.index_html())
             .await
             .context(CommonIoSnafu)?;
         Ok(())
     }
 
-    pub async fn rewrite_swagger_initializer_js(&self) -> Result<()> {
-        let js = format!(
+    /// content of the initializer js
+    pub fn initializer_js(&self) -> String {
+        format!(
             r#"window.onload = function() {{
   //<editor-fold desc="Changeable Configuration Block">
 
@@ -175,9 +179,12 @@ impl InitSwagger {
   //</editor-fold>
 }};"#,
             &self.json_uri
-        );
+        )
+    }
+
+    pub async fn rewrite_swagger_initializer_js(&self) -> Result<()> {
         tracing::info!("write js initializer path: {}", self.js_filepath());
-        tokio::fs::write(&self.js_filepath(), js)
+        tokio::fs::write(&self.js_filepath(), self.initializer_js())
             .await
             .context(CommonIoSnafu)?;
         Ok(())
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use once_cell::sync::OnceCell;
+use snafu::{OptionExt, ResultExt};
+
+use crate::error::{
+    CommonIoSnafu, PublicKeyAlreadySetSnafu, PublicKeyInvalidSnafu, Result, SignatureInvalidSnafu,
+    SignatureMissingSnafu, UpdateUnsignedSnafu,
+};
+
+/// detached signature of `agent` is `agent.sig`
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// hex public key compiled in when set at build time
+pub const PUBLIC_KEY_ENV: &str = "AWESOME_OPERATES_UPDATE_PUBLIC_KEY";
+
+static PUBLIC_KEY: OnceCell<VerifyingKey> = OnceCell::new();
+
+static ALLOW_UNSIGNED: AtomicBool = AtomicBool::new(false);
+
+/// use the application's compiled-in key, like `set_public_key(include_bytes!("update.pub"))`
+/// call it before any verification, fails when a key is already set or loaded from `PUBLIC_KEY_ENV`
+pub fn set_public_key(bytes: &[u8; PUBLIC_KEY_LENGTH]) -> Result<()> {
+    let key = VerifyingKey::from_bytes(bytes).context(PublicKeyInvalidSnafu)?;
+    PUBLIC_KEY
+        .set(key)
+        .map_err(|_| PublicKeyAlreadySetSnafu.build())
+}
+
+/// install updates without verification when no public key is configured, refused by default
+pub fn allow_unsigned_updates(allow: bool) {
+    ALLOW_UNSIGNED.store(allow, Ordering::Relaxed);
+}
+
+/// key set by `set_public_key`, or the one from `PUBLIC_KEY_ENV` at build time
+pub fn public_key() -> Option<VerifyingKey> {
+    PUBLIC_KEY
+        .get_or_try_init(|| {
+            let hex_key = option_env!("AWESOME_OPERATES_UPDATE_PUBLIC_KEY").ok_or(())?;
+            let bytes = hex::decode(hex_key.trim()).map_err(|_| ())?;
+            let bytes = <[u8; PUBLIC_KEY_LENGTH]>::try_from(bytes).map_err(|_| ())?;
+            VerifyingKey::from_bytes(&bytes).map_err(|_| ())
+        })
+        .ok()
+        .copied()
+}
+
+pub fn signature_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_os_string();
+    path.push(SIGNATURE_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// raw 64 bytes, or hex text
+pub fn parse_signature(content: &[u8]) -> Option<Signature> {
+    let bytes = if content.len() == SIGNATURE_LENGTH {
+        content.to_vec()
+    } else {
+        hex::decode(String::from_utf8_lossy(content).trim()).ok()?
+    };
+    Some(Signature::from_bytes(&bytes.try_into().ok()?))
+}
+
+/// verify `path` with the detached signature `path.sig`
+pub async fn verify_file_with_key(path: impl AsRef<Path>, key: &VerifyingKey) -> Result<()> {
+    let path = path.as_ref();
+    let data = tokio::fs::read(path).await.context(CommonIoSnafu)?;
+    verify_data_with_key(path, &data, key).await
+}
+
+/// verify `data` read from `path` with the detached signature `path.sig`
+pub async fn verify_data_with_key(
+    path: impl AsRef<Path>,
+    data: &[u8],
+    key: &VerifyingKey,
+) -> Result<()> {
+    let path = path.as_ref();
+    let filepath = path.display().to_string();
+    let signature_path = signature_path(path);
+    if !signature_path.exists() {
+        return SignatureMissingSnafu { filepath }.fail();
+    }
+    let content = tokio::fs::read(&signature_path)
+        .await
+        .context(CommonIoSnafu)?;
+    let signature = parse_signature(&content).context(SignatureMissingSnafu {
+        filepath: &filepath,
+    })?;
+    key.verify(data, &signature)
+        .context(SignatureInvalidSnafu { filepath })
+}
+
+/// verify an update file by `public_key`, refuse unsigned or tampered files
+/// without a public key, refuse it unless `allow_unsigned_updates` and return `false`
+pub async fn verify_update_file(path: impl AsRef<Path>) -> Result<bool> {
+    let path = path.as_ref();
+    let data = tokio::fs::read(path).await.context(CommonIoSnafu)?;
+    verify_update_data(path, &data).await
+}
+
+/// `verify_update_file` on the bytes read from `path`, so the verified bytes are the installed ones
+pub async fn verify_update_data(path: impl AsRef<Path>, data: &[u8]) -> Result<bool> {
+    let path = path.as_ref();
+    let Some(key) = public_key() else {
+        if !ALLOW_UNSIGNED.load(Ordering::Relaxed) {
+            tracing::error!(
+                "refuse update {}, no update public key configured",
+                path.display()
+            );
+            return UpdateUnsignedSnafu {
+                filepath: path.display().to_string(),
+            }
+            .fail();
+        }
+        tracing::warn!(
+            "no update public key configured, skip signature verification of {}",
+            path.display()
+        );
+        return Ok(false);
+    };
+    if let Err(e) = verify_data_with_key(path, data, &key).await {
+        tracing::error!("refuse update {}: {e}", path.display());
+        return Err(e);
+    }
+    tracing::info!("signature of {} verified", path.display());
+    Ok(true)
+}
+
+/// verify `src` and write the verified bytes over `dst` by a rename, then remove `src`
+/// the bytes are read once, so `src` changed after verifying is never installed
+pub async fn install_update_file(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<bool> {
+    stage_update_file(src, dst).await?.install().await
+}
+
+/// verify `src` and write the verified bytes beside `dst` as `{dst}.new`
+/// check the staged file, e.g. run it, then `install` or `discard` it without verifying again
+pub async fn stage_update_file(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+) -> Result<VerifiedUpdate> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let data = tokio::fs::read(src).await.context(CommonIoSnafu)?;
+    let signed = verify_update_data(src, &data).await?;
+    let permissions = tokio::fs::metadata(src)
+        .await
+        .context(CommonIoSnafu)?
+        .permissions();
+    let mut staged = dst.as_os_str().to_os_string();
+    staged.push(".new");
+    let staged = PathBuf::from(staged);
+    tokio::fs::write(&staged, &data)
+        .await
+        .context(CommonIoSnafu)?;
+    tokio::fs::set_permissions(&staged, permissions)
+        .await
+        .context(CommonIoSnafu)?;
+    Ok(VerifiedUpdate {
+        src: src.to_path_buf(),
+        staged,
+        dst: dst.to_path_buf(),
+        signed,
+    })
+}
+
+/// verified bytes of an update file staged by `stage_update_file`
+#[derive(Debug)]
+pub struct VerifiedUpdate {
+    src: PathBuf,
+    staged: PathBuf,
+    dst: PathBuf,
+    signed: bool,
+}
+
+impl VerifiedUpdate {
+    /// the staged file, its bytes are the verified ones
+    pub fn path(&self) -> &Path {
+        &self.staged
+    }
+
+    /// `false` when it's allowed unsigned, see `allow_unsigned_updates`
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// rename the staged file over the target and remove the update file
+    pub async fn install(self) -> Result<bool> {
+        tokio::fs::rename(&self.staged, &self.dst)
+            .await
+            .context(CommonIoSnafu)?;
+        tokio::fs::remove_file(&self.src)
+            .await
+            .context(CommonIoSnafu)?;
+        Ok(self.signed)
+    }
+
+    /// remove the staged file, the update file is kept
+    pub async fn discard(self) -> Result<()> {
+        tokio::fs::remove_file(&self.staged)
+            .await
+            .context(CommonIoSnafu)
+    }
+}
+
+/// sign with it in tests, its verifying key is set as the public key
+#[cfg(test)]
+pub(crate) fn test_signing_key() -> ed25519_dalek::SigningKey {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    let signing = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    INIT.call_once(|| set_public_key(signing.verifying_key().as_bytes()).unwrap());
+    signing
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::Signer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn verify_detached_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent");
+        tokio::fs::write(&path, "binary").await.unwrap();
+        let signing = test_signing_key();
+        let key = signing.verifying_key();
+        assert!(matches!(
+            set_public_key(key.as_bytes()),
+            Err(crate::error::AppError::PublicKeyAlreadySet { .. })
+        ));
+        assert!(verify_file_with_key(&path, &key).await.is_err());
+
+        let signature = signing.sign(b"binary");
+        tokio::fs::write(signature_path(&path), hex::encode(signature.to_bytes()))
+            .await
+            .unwrap();
+        verify_file_with_key(&path, &key).await.unwrap();
+        tokio::fs::write(signature_path(&path), signature.to_bytes())
+            .await
+            .unwrap();
+        verify_file_with_key(&path, &key).await.unwrap();
+
+        let dst = dir.path().join("installed");
+        assert!(install_update_file(&path, &dst).await.unwrap());
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), b"binary");
+        assert!(!path.exists());
+
+        tokio::fs::write(&path, "tampered").await.unwrap();
+        assert!(verify_file_with_key(&path, &key).await.is_err());
+        assert!(install_update_file(&path, &dst).await.is_err());
+        assert_eq!(tokio::fs::read(&dst).await.unwrap(), b"binary");
+    }
+}